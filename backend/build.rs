@@ -1,5 +1,3 @@
-#![feature(try_blocks)]
-
 use std::{
     cell::RefCell,
     collections::HashMap,
@@ -18,16 +16,25 @@ fn main() -> Result<()> {
     println!("cargo:rerun-if-changed=../proto");
     println!("cargo:rerun-if-changed=../template");
     println!("cargo:rerun-if-changed=../asset");
+    println!("cargo:rerun-if-changed=../mime.types");
 
     build_proto()?;
     load_template()?;
     load_asset()?;
+    load_mime_types()?;
 
     Ok(())
 }
 
 fn build_proto() -> Result<()> {
-    compile_protos(&["../proto/search.proto"], &["../proto/"])?;
+    compile_protos(
+        &[
+            "../proto/search.proto",
+            "../proto/article.proto",
+            "../proto/media.proto",
+        ],
+        &["../proto/"],
+    )?;
     Ok(())
 }
 
@@ -36,16 +43,16 @@ fn load_template() -> Result<()> {
     let out_path = Path::new(&out_path).join("template.rs");
 
     let mut file = File::create(out_path)?;
-    file.write_all(b"pub const TEMPLATES: &'static [(&'static str, &'static [u8])] = &[\n")?;
+    file.write_all(b"pub const TEMPLATES: &[(&str, &[u8])] = &[\n")?;
 
     let source = Path::new("../template");
 
     let paths = read_dir(source)?
-        .map(|e| try {
+        .map(|e| -> Result<String> {
             let e = e?;
             let path = e.path();
             let path = path.to_str().ok_or(anyhow!("path is not utf8"))?;
-            path.to_string()
+            Ok(path.to_string())
         })
         .collect::<Result<Vec<_>>>()?;
 
@@ -76,9 +83,7 @@ fn load_asset() -> Result<()> {
     let out_path = Path::new(&out_path).join("asset.rs");
 
     let mut file = File::create(out_path)?;
-    file.write_all(
-        b"pub const ASSETS: &'static [(&'static str, &'static [u8], &'static str)] = &[\n",
-    )?;
+    file.write_all(b"pub const ASSETS: &[(&str, &[u8], &str)] = &[\n")?;
 
     for path in assets {
         let mut asset = File::open(&path)?;
@@ -110,6 +115,27 @@ fn load_asset() -> Result<()> {
     Ok(())
 }
 
+// The same table is used at runtime to serve uploaded media
+fn load_mime_types() -> Result<()> {
+    let mut mime_types: Vec<_> = read_mime_types()?.into_iter().collect();
+    mime_types.sort();
+
+    let out_path = env::var("OUT_DIR")?;
+    let out_path = Path::new(&out_path).join("mime.rs");
+
+    let mut file = File::create(out_path)?;
+    file.write_all(b"pub const MIME_TYPES: &[(&str, &str)] = &[\n")?;
+
+    for (ext, mime_type) in mime_types {
+        let entry = format!("    (\"{ext}\", \"{mime_type}\"),\n");
+        file.write_all(entry.as_bytes())?;
+    }
+
+    file.write_all(b"];\n")?;
+
+    Ok(())
+}
+
 fn find_asset(path: &str, assets: &RefCell<Vec<String>>) -> Result<()> {
     let path = Path::new(path);
 
@@ -137,10 +163,9 @@ fn read_mime_types() -> Result<HashMap<String, String>> {
     mime_types.read_to_end(&mut buffer)?;
 
     let mime_types: HashMap<_, _> = String::from_utf8(buffer)?
-        .split("\n")
-        .into_iter()
+        .split('\n')
         .map(|s| s.trim())
-        .filter(|s| s.len() > 0)
+        .filter(|s| !s.is_empty())
         .filter(|s| !s.starts_with('#'))
         .flat_map(|s| {
             let line: Vec<_> = s.split_whitespace().collect();
@@ -157,6 +182,6 @@ fn read_mime_types() -> Result<HashMap<String, String>> {
 }
 
 pub fn minify_html(orig: Vec<u8>) -> Vec<u8> {
-    static CFG: Lazy<Cfg> = Lazy::new(|| Cfg::spec_compliant());
+    static CFG: Lazy<Cfg> = Lazy::new(Cfg::spec_compliant);
     minify(&orig, &CFG)
 }
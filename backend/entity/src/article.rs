@@ -15,14 +15,25 @@ pub struct Model {
     pub created: i64,
     pub updated: Option<i64>,
     pub r#type: i32,
+    pub scheduled: Option<i64>,
+    pub kind: i32,
+    pub author_id: Option<i64>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
 pub enum Relation {
+    #[sea_orm(has_many = "super::article_revision::Entity")]
+    ArticleRevision,
     #[sea_orm(has_many = "super::article_tag::Entity")]
     ArticleTag,
 }
 
+impl Related<super::article_revision::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::ArticleRevision.def()
+    }
+}
+
 impl Related<super::article_tag::Entity> for Entity {
     fn to() -> RelationDef {
         Relation::ArticleTag.def()
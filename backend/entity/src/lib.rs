@@ -2,7 +2,13 @@
 
 pub mod prelude;
 
+pub mod api_token;
 pub mod article;
+pub mod article_revision;
 pub mod article_tag;
+pub mod ip_ban;
+pub mod media_image;
 pub mod metadata;
+pub mod session;
 pub mod tag;
+pub mod user;
@@ -1,6 +1,12 @@
 //! `SeaORM` Entity. Generated by sea-orm-codegen 0.12.11
 
+pub use super::api_token::Entity as ApiToken;
 pub use super::article::Entity as Article;
+pub use super::article_revision::Entity as ArticleRevision;
 pub use super::article_tag::Entity as ArticleTag;
+pub use super::ip_ban::Entity as IpBan;
+pub use super::media_image::Entity as MediaImage;
 pub use super::metadata::Entity as Metadata;
+pub use super::session::Entity as Session;
 pub use super::tag::Entity as Tag;
+pub use super::user::Entity as User;
@@ -1,7 +1,20 @@
+#![allow(clippy::enum_variant_names)]
+
 pub use sea_orm_migration::prelude::*;
 
 mod m20240118_000001_create_table;
 mod m20240204_000001_create_table;
+mod m20240301_000001_rename_tag_column;
+mod m20240315_000001_create_article_fts;
+mod m20240401_000001_add_article_scheduled;
+mod m20240415_000001_add_article_kind;
+mod m20240501_000001_create_article_revision;
+mod m20240515_000001_create_media_image;
+mod m20240601_000001_create_session;
+mod m20240615_000001_create_user;
+mod m20240701_000001_create_ip_ban;
+mod m20240715_000001_create_api_token;
+mod m20240801_000001_add_user_two_factor;
 
 pub struct Migrator;
 
@@ -11,6 +24,17 @@ impl MigratorTrait for Migrator {
         vec![
             Box::new(m20240118_000001_create_table::Migration),
             Box::new(m20240204_000001_create_table::Migration),
+            Box::new(m20240301_000001_rename_tag_column::Migration),
+            Box::new(m20240315_000001_create_article_fts::Migration),
+            Box::new(m20240401_000001_add_article_scheduled::Migration),
+            Box::new(m20240415_000001_add_article_kind::Migration),
+            Box::new(m20240501_000001_create_article_revision::Migration),
+            Box::new(m20240515_000001_create_media_image::Migration),
+            Box::new(m20240601_000001_create_session::Migration),
+            Box::new(m20240615_000001_create_user::Migration),
+            Box::new(m20240701_000001_create_ip_ban::Migration),
+            Box::new(m20240715_000001_create_api_token::Migration),
+            Box::new(m20240801_000001_add_user_two_factor::Migration),
         ]
     }
 }
@@ -0,0 +1,60 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+// Full text index over the article table, kept in sync by triggers. The
+// trigram tokenizer is used since unicode61 cannot segment CJK text.
+const UP: &[&str] = &[
+    "CREATE VIRTUAL TABLE IF NOT EXISTS article_fts USING fts5(
+        title, markdown,
+        content='article', content_rowid='article_id',
+        tokenize='trigram'
+    )",
+    "CREATE TRIGGER IF NOT EXISTS article_fts_insert AFTER INSERT ON article BEGIN
+        INSERT INTO article_fts(rowid, title, markdown)
+            VALUES (new.article_id, new.title, new.markdown);
+    END",
+    "CREATE TRIGGER IF NOT EXISTS article_fts_delete AFTER DELETE ON article BEGIN
+        INSERT INTO article_fts(article_fts, rowid, title, markdown)
+            VALUES ('delete', old.article_id, old.title, old.markdown);
+    END",
+    "CREATE TRIGGER IF NOT EXISTS article_fts_update AFTER UPDATE ON article BEGIN
+        INSERT INTO article_fts(article_fts, rowid, title, markdown)
+            VALUES ('delete', old.article_id, old.title, old.markdown);
+        INSERT INTO article_fts(rowid, title, markdown)
+            VALUES (new.article_id, new.title, new.markdown);
+    END",
+    // Index the articles written before this migration
+    "INSERT INTO article_fts(article_fts) VALUES ('rebuild')",
+];
+
+const DOWN: &[&str] = &[
+    "DROP TRIGGER IF EXISTS article_fts_insert",
+    "DROP TRIGGER IF EXISTS article_fts_delete",
+    "DROP TRIGGER IF EXISTS article_fts_update",
+    "DROP TABLE IF EXISTS article_fts",
+];
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        for statement in UP {
+            db.execute_unprepared(statement).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        for statement in DOWN {
+            db.execute_unprepared(statement).await?;
+        }
+
+        Ok(())
+    }
+}
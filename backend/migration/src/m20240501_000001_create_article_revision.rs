@@ -0,0 +1,84 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(ArticleRevision::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(ArticleRevision::RevisionId)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(ArticleRevision::ArticleId)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(ArticleRevision::Title).string().not_null())
+                    .col(
+                        ColumnDef::new(ArticleRevision::Markdown)
+                            .string()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(ArticleRevision::Excerpt).string().not_null())
+                    .col(
+                        ColumnDef::new(ArticleRevision::Created)
+                            .integer()
+                            .not_null(),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(ArticleRevision::Table, ArticleRevision::ArticleId)
+                            .to(Article::Table, Article::ArticleId),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_article_revision_article_id")
+                    .table(ArticleRevision::Table)
+                    .col(ArticleRevision::ArticleId)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(ArticleRevision::Table).to_owned())
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum Article {
+    Table,
+    ArticleId,
+}
+
+#[derive(DeriveIden)]
+enum ArticleRevision {
+    Table,
+    RevisionId,
+    ArticleId,
+    Title,
+    Markdown,
+    Excerpt,
+    Created,
+}
@@ -0,0 +1,56 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(MediaImage::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(MediaImage::File)
+                            .string()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(MediaImage::Source).string().not_null())
+                    .col(ColumnDef::new(MediaImage::Width).integer().not_null())
+                    .col(ColumnDef::new(MediaImage::Height).integer().not_null())
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_media_image_source")
+                    .table(MediaImage::Table)
+                    .col(MediaImage::Source)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(MediaImage::Table).to_owned())
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum MediaImage {
+    Table,
+    File,
+    Source,
+    Width,
+    Height,
+}
@@ -0,0 +1,41 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(IpBan::Table)
+                    .if_not_exists()
+                    .col(ColumnDef::new(IpBan::Ip).string().not_null().primary_key())
+                    .col(ColumnDef::new(IpBan::Failures).integer().not_null())
+                    .col(ColumnDef::new(IpBan::LastFailure).integer().not_null())
+                    .col(ColumnDef::new(IpBan::BannedUntil).integer())
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(IpBan::Table).to_owned())
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum IpBan {
+    Table,
+    Ip,
+    Failures,
+    LastFailure,
+    BannedUntil,
+}
@@ -0,0 +1,58 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(ApiToken::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(ApiToken::TokenId)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(ApiToken::UserId).integer().not_null())
+                    .col(ColumnDef::new(ApiToken::Name).string().not_null())
+                    .col(
+                        ColumnDef::new(ApiToken::TokenHash)
+                            .string()
+                            .not_null()
+                            .unique_key(),
+                    )
+                    .col(ColumnDef::new(ApiToken::Scopes).string().not_null())
+                    .col(ColumnDef::new(ApiToken::Created).integer().not_null())
+                    .col(ColumnDef::new(ApiToken::LastUsed).integer())
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(ApiToken::Table).to_owned())
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum ApiToken {
+    Table,
+    TokenId,
+    UserId,
+    Name,
+    TokenHash,
+    Scopes,
+    Created,
+    LastUsed,
+}
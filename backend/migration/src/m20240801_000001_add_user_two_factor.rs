@@ -0,0 +1,57 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // SQLite only adds one column per statement
+        for mut column in [
+            ColumnDef::new(User::TotpSecret).string().to_owned(),
+            ColumnDef::new(User::TotpPendingSecret).string().to_owned(),
+            ColumnDef::new(User::TotpRecoveryCodes).string().to_owned(),
+            ColumnDef::new(User::TotpLastStep).integer().to_owned(),
+        ] {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(User::Table)
+                        .add_column(&mut column)
+                        .to_owned(),
+                )
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        for column in [
+            User::TotpSecret,
+            User::TotpPendingSecret,
+            User::TotpRecoveryCodes,
+            User::TotpLastStep,
+        ] {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(User::Table)
+                        .drop_column(column)
+                        .to_owned(),
+                )
+                .await?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum User {
+    Table,
+    TotpSecret,
+    TotpPendingSecret,
+    TotpRecoveryCodes,
+    TotpLastStep,
+}
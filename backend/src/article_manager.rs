@@ -1,16 +1,29 @@
 use anyhow::Result;
 use chrono::{Datelike, Local, TimeZone};
 use hashbrown::HashMap;
-use log::{debug, warn};
+use log::{debug, info, warn};
 use parking_lot::RwLock;
-use sea_orm::{ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter};
+use sea_orm::{
+    sea_query::Expr, ActiveModelTrait, ColumnTrait, Condition, ConnectionTrait, DatabaseConnection,
+    DbBackend, EntityTrait, FromQueryResult, QueryFilter, QueryOrder, Set, Statement,
+    TransactionTrait,
+};
 use serde::Serialize;
+use tera::escape_html;
 
 use entity::{
     article::{self, Model as Article},
-    prelude::Article as ArticleEntity,
+    article_revision::{self, Model as Revision},
+    article_tag,
+    prelude::{
+        Article as ArticleEntity, ArticleRevision as ArticleRevisionEntity,
+        ArticleTag as ArticleTagEntity, Tag as TagEntity,
+    },
+    tag,
 };
 
+use crate::{markdown, util::*};
+
 pub struct ArticleManager {
     db: DatabaseConnection,
     cache: RwLock<HashMap<String, Article>>,
@@ -24,6 +37,169 @@ pub struct ArticleMetadata {
     pub updated: Option<String>,
     pub url: String,
     pub year: u64,
+    pub tags: Vec<String>,
+    #[serde(skip)]
+    pub last_modified: i64,
+}
+
+pub struct SearchHit {
+    pub article: Article,
+    pub score: f64,
+    // HTML with matches wrapped in <mark>
+    pub snippet: String,
+}
+
+#[derive(Serialize)]
+pub struct TagCount {
+    pub tag: String,
+    pub count: usize,
+}
+
+// Stored in the `type` and `scheduled` columns of the article table
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ArticleStatus {
+    Published,
+    Draft,
+    // Reachable by url, but left out of listings, feeds and search
+    Unlisted,
+    // Published once the timestamp has passed
+    Scheduled(i64),
+}
+
+impl ArticleStatus {
+    pub fn of(article: &Article) -> Self {
+        match (article.r#type, article.scheduled) {
+            (0, _) => Self::Published,
+            (1, _) => Self::Draft,
+            (2, _) => Self::Unlisted,
+            (3, Some(scheduled)) => Self::Scheduled(scheduled),
+            (value, _) => {
+                warn!("Unknown article type {value}, treating as draft");
+                Self::Draft
+            }
+        }
+    }
+
+    // Values for the `type` and `scheduled` columns
+    fn columns(self) -> (i32, Option<i64>) {
+        match self {
+            Self::Published => (0, None),
+            Self::Draft => (1, None),
+            Self::Unlisted => (2, None),
+            Self::Scheduled(scheduled) => (3, Some(scheduled)),
+        }
+    }
+
+    // Whether anonymous visitors can open the article by its url
+    pub fn is_visible(self, now: i64) -> bool {
+        match self {
+            Self::Published | Self::Unlisted => true,
+            Self::Draft => false,
+            Self::Scheduled(scheduled) => scheduled <= now,
+        }
+    }
+
+    // Whether the article shows up in listings, feeds, search and the sitemap
+    pub fn is_listed(self, now: i64) -> bool {
+        match self {
+            Self::Published => true,
+            Self::Draft | Self::Unlisted => false,
+            Self::Scheduled(scheduled) => scheduled <= now,
+        }
+    }
+}
+
+// Stored in the `kind` column of the article table
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ArticleKind {
+    Post,
+    // Standalone page served at the top level, kept out of the blog timeline
+    Page,
+}
+
+impl ArticleKind {
+    pub fn of(article: &Article) -> Self {
+        match article.kind {
+            0 => Self::Post,
+            1 => Self::Page,
+            value => {
+                warn!("Unknown article kind {value}, treating as post");
+                Self::Post
+            }
+        }
+    }
+
+    fn column(self) -> i32 {
+        match self {
+            Self::Post => 0,
+            Self::Page => 1,
+        }
+    }
+}
+
+// Top level paths taken by other routes, pages cannot use them. Every new top level route
+// belongs here, `dev` too even though it is only routed in dev mode.
+const RESERVED_PAGE_URLS: &[&str] = &[
+    "api",
+    "archive",
+    "article",
+    "asset",
+    "atom.xml",
+    "dev",
+    "editor",
+    "feed.xml",
+    "login",
+    "logout",
+    "media",
+    "page",
+    "robots.txt",
+    "search",
+    "sitemap.xml",
+    "tag",
+    "tags",
+];
+
+// Search terms shorter than this are not in the trigram index
+const MIN_INDEXED_TERM: usize = 3;
+
+// Wrap matches in search snippets until they are escaped. Control characters never appear in
+// articles, so they are safe as markers.
+const MARK_START: &str = "\u{2}";
+const MARK_END: &str = "\u{3}";
+
+// Who is reading, anonymous visitors only get what has been published
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Audience {
+    Public,
+    Editor,
+}
+
+impl Audience {
+    fn can_open(self, article: &Article) -> bool {
+        self == Self::Editor || ArticleStatus::of(article).is_visible(timestamp())
+    }
+
+    fn can_list(self, article: &Article) -> bool {
+        self == Self::Editor || ArticleStatus::of(article).is_listed(timestamp())
+    }
+}
+
+// Fields accepted by the write path, `None` means "leave unchanged" on update
+#[derive(Default)]
+pub struct ArticleFields {
+    pub title: Option<String>,
+    pub url: Option<String>,
+    pub markdown: Option<String>,
+    pub excerpt: Option<String>,
+    pub status: Option<ArticleStatus>,
+    pub kind: Option<ArticleKind>,
+    pub tags: Option<Vec<String>>,
+    // The user the article belongs to, taken from whoever creates it
+    pub author_id: Option<i64>,
+    // Only set by imports, the editor leaves the timestamps to us. `Some(None)` keeps an
+    // article that was never edited that way.
+    pub created: Option<i64>,
+    pub updated: Option<Option<i64>>,
 }
 
 impl ArticleManager {
@@ -32,7 +208,14 @@ impl ArticleManager {
         Ok(Self { db, cache })
     }
 
-    pub async fn get_article(&self, url: &str) -> Result<Option<Article>> {
+    pub async fn get_article(&self, url: &str, audience: Audience) -> Result<Option<Article>> {
+        // Visibility depends on the clock, so it is checked on every read
+        let article = self.get_article_unchecked(url).await?;
+
+        Ok(article.filter(|article| audience.can_open(article)))
+    }
+
+    async fn get_article_unchecked(&self, url: &str) -> Result<Option<Article>> {
         // First, check the cache
         if let Some(article) = self.cache.read().get(url) {
             debug!("Cache hit for article {url}");
@@ -45,8 +228,7 @@ impl ArticleManager {
         let article: Option<Article> = ArticleEntity::find()
             .filter(article::Column::Url.eq(url))
             .one(&self.db)
-            .await?
-            .map(|article| article.into());
+            .await?;
 
         // Update the cache
         if let Some(article) = &article {
@@ -66,55 +248,640 @@ impl ArticleManager {
         Ok(Some(article))
     }
 
-    pub async fn search(&self, query: &str) -> Result<Vec<Article>> {
-        let articles: Vec<Article> = ArticleEntity::find()
-            .filter(
-                article::Column::Title
-                    .contains(query)
-                    .or(article::Column::Content.contains(query)),
-            )
+    pub async fn search(
+        &self,
+        query: &str,
+        tag: Option<&str>,
+        audience: Audience,
+    ) -> Result<Vec<SearchHit>> {
+        let mut hits = self.search_all(query).await?;
+        hits.retain(|hit| {
+            ArticleKind::of(&hit.article) == ArticleKind::Post && audience.can_list(&hit.article)
+        });
+
+        if let Some(tag) = tag {
+            let ids = self.get_article_ids_by_tag(tag).await?;
+            hits.retain(|hit| ids.contains(&hit.article.article_id));
+        }
+
+        Ok(hits)
+    }
+
+    async fn search_all(&self, query: &str) -> Result<Vec<SearchHit>> {
+        let terms: Vec<&str> = query.split_whitespace().collect();
+        if terms.is_empty() {
+            return Ok(vec![]);
+        }
+
+        // The trigram tokenizer cannot match anything shorter than three characters, like most
+        // Chinese words. Those are looked for among what the index finds for the other terms.
+        let (indexed, short): (Vec<&str>, Vec<&str>) = terms
+            .iter()
+            .partition(|term| term.chars().count() >= MIN_INDEXED_TERM);
+
+        let hits = match indexed.is_empty() {
+            true => self.search_unindexed(&short).await?,
+            false => self.search_indexed(&indexed, &short).await?,
+        };
+
+        debug!("Found {} article(s) matching query {query}", hits.len());
+
+        Ok(hits)
+    }
+
+    async fn search_indexed(&self, terms: &[&str], short: &[&str]) -> Result<Vec<SearchHit>> {
+        const SQL: &str = "
+            SELECT rowid AS article_id,
+                   -bm25(article_fts, 10.0, 1.0) AS score,
+                   snippet(article_fts, -1, $1, $2, '…', 24) AS snippet
+            FROM article_fts
+            WHERE article_fts MATCH $3
+            ORDER BY score DESC";
+
+        #[derive(FromQueryResult)]
+        struct Row {
+            article_id: i64,
+            score: f64,
+            snippet: String,
+        }
+
+        // Quote every term so FTS5 query syntax in user input is taken literally
+        let fts_query = terms
+            .iter()
+            .map(|term| format!("\"{}\"", term.replace('"', "\"\"")))
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        let statement = Statement::from_sql_and_values(
+            DbBackend::Sqlite,
+            SQL,
+            [MARK_START.into(), MARK_END.into(), fts_query.into()],
+        );
+
+        let rows = Row::find_by_statement(statement).all(&self.db).await?;
+
+        let ids: Vec<_> = rows.iter().map(|row| row.article_id).collect();
+        let mut articles: HashMap<i64, Article> = ArticleEntity::find()
+            .filter(article::Column::ArticleId.is_in(ids))
             .all(&self.db)
             .await?
             .into_iter()
-            .map(|article| article.into())
+            .map(|article| (article.article_id, article))
             .collect();
 
-        debug!("Found {} article(s) matching query {query}", articles.len());
+        let short: Vec<Vec<char>> = short.iter().map(|term| term.chars().collect()).collect();
 
-        Ok(articles)
+        let hits: Vec<_> = rows
+            .into_iter()
+            .filter_map(|row| {
+                let article = articles.remove(&row.article_id)?;
+
+                let found = short.iter().all(|term| {
+                    count_term(&article.title, term) + count_term(&article.markdown, term) > 0
+                });
+                if !found {
+                    return None;
+                }
+
+                let snippet: Vec<char> = row.snippet.chars().collect();
+
+                Some(SearchHit {
+                    article,
+                    score: row.score,
+                    snippet: render_snippet(&snippet, &short),
+                })
+            })
+            .collect();
+
+        Ok(hits)
     }
 
-    pub fn _remove(&self, article: &str) {
+    // Only terms too short for the index, ranked by how often they appear, in titles most of all
+    // like with bm25 above
+    async fn search_unindexed(&self, terms: &[&str]) -> Result<Vec<SearchHit>> {
+        // LIKE only ignores the case of ASCII letters, so it can narrow things down just for
+        // terms without other letters that have a case. The rest is checked below.
+        let mut condition = Condition::all();
+        for term in terms.iter().filter(|term| {
+            term.chars()
+                .all(|c| c.is_ascii() || !(c.is_lowercase() || c.is_uppercase()))
+        }) {
+            condition = condition.add(
+                article::Column::Title
+                    .contains(*term)
+                    .or(article::Column::Markdown.contains(*term)),
+            );
+        }
+
+        let articles = ArticleEntity::find()
+            .filter(condition)
+            .all(&self.db)
+            .await?;
+
+        let terms: Vec<Vec<char>> = terms.iter().map(|term| term.chars().collect()).collect();
+
+        let mut hits: Vec<_> = articles
+            .into_iter()
+            .filter_map(|article| {
+                let mut score = 0.0;
+
+                for term in &terms {
+                    let title = count_term(&article.title, term);
+                    let markdown = count_term(&article.markdown, term);
+
+                    if title + markdown == 0 {
+                        return None;
+                    }
+
+                    score += (10 * title + markdown) as f64;
+                }
+
+                // Long articles mention everything now and then
+                let length = article.markdown.chars().count() as f64;
+                let score = score / (1.0 + length / 1000.0);
+
+                let markdown: Vec<char> = article.markdown.chars().collect();
+                let snippet = render_snippet(&snippet_of(&markdown, &terms), &terms);
+
+                Some(SearchHit {
+                    article,
+                    score,
+                    snippet,
+                })
+            })
+            .collect();
+
+        hits.sort_by(|a, b| b.score.total_cmp(&a.score));
+
+        Ok(hits)
+    }
+
+    pub fn remove(&self, article: &str) {
         self.cache.write().remove(article);
     }
 
-    pub async fn get_all_article_metadatas(&self) -> Result<Vec<ArticleMetadata>> {
-        let mut articles: Vec<Article> = ArticleEntity::find()
+    pub async fn create_article(&self, fields: ArticleFields) -> Result<Article> {
+        let url = match fields.url {
+            Some(url) if !url.is_empty() => url,
+            _ => return Err(invalid("article url is required")),
+        };
+
+        let title = fields.title.ok_or(invalid("article title is required"))?;
+
+        if self.url_exists(&url).await? {
+            return Err(invalid(format!("article with url {url} already exists")));
+        }
+
+        let kind = fields.kind.unwrap_or(ArticleKind::Post);
+        check_page_url(kind, &url)?;
+
+        let markdown = fields.markdown.unwrap_or_default();
+        let content = markdown::render(&markdown);
+
+        let (r#type, scheduled) = fields.status.unwrap_or(ArticleStatus::Draft).columns();
+
+        let article = article::ActiveModel {
+            title: Set(title),
+            url: Set(url),
+            markdown: Set(markdown),
+            content: Set(content),
+            excerpt: Set(fields.excerpt.unwrap_or_default()),
+            created: Set(fields.created.unwrap_or_else(timestamp)),
+            updated: Set(fields.updated.flatten()),
+            r#type: Set(r#type),
+            scheduled: Set(scheduled),
+            kind: Set(kind.column()),
+            author_id: Set(fields.author_id),
+            ..Default::default()
+        };
+
+        let txn = self.db.begin().await?;
+
+        let article = article.insert(&txn).await?;
+
+        if let Some(tags) = fields.tags {
+            set_tags(&txn, article.article_id, tags).await?;
+        }
+
+        txn.commit().await?;
+
+        info!("Created article {}", article.url);
+
+        Ok(article)
+    }
+
+    pub async fn update_article(
+        &self,
+        url: &str,
+        fields: ArticleFields,
+    ) -> Result<Option<Article>> {
+        let article = ArticleEntity::find()
+            .filter(article::Column::Url.eq(url))
+            .one(&self.db)
+            .await?;
+
+        let article = match article {
+            Some(article) => article,
+            None => {
+                debug!("No article found with url {url}");
+                return Ok(None);
+            }
+        };
+
+        let kind = fields.kind.unwrap_or(ArticleKind::of(&article));
+        check_page_url(kind, fields.url.as_deref().unwrap_or(url))?;
+
+        // Keep what is about to be overwritten, unless only the metadata changes
+        let changed = fields.title.as_ref().is_some_and(|t| *t != article.title)
+            || fields
+                .markdown
+                .as_ref()
+                .is_some_and(|m| *m != article.markdown)
+            || fields
+                .excerpt
+                .as_ref()
+                .is_some_and(|e| *e != article.excerpt);
+
+        let revision = article_revision::ActiveModel {
+            article_id: Set(article.article_id),
+            title: Set(article.title.clone()),
+            markdown: Set(article.markdown.clone()),
+            excerpt: Set(article.excerpt.clone()),
+            created: Set(article.updated.unwrap_or(article.created)),
+            ..Default::default()
+        };
+
+        let mut active: article::ActiveModel = article.into();
+        active.kind = Set(kind.column());
+
+        if let Some(new_url) = fields.url {
+            if new_url.is_empty() {
+                return Err(invalid("article url cannot be empty"));
+            }
+
+            if new_url != url && self.url_exists(&new_url).await? {
+                return Err(invalid(format!(
+                    "article with url {new_url} already exists"
+                )));
+            }
+
+            active.url = Set(new_url);
+        }
+
+        if let Some(title) = fields.title {
+            active.title = Set(title);
+        }
+
+        if let Some(markdown) = fields.markdown {
+            active.content = Set(markdown::render(&markdown));
+            active.markdown = Set(markdown);
+        }
+
+        if let Some(excerpt) = fields.excerpt {
+            active.excerpt = Set(excerpt);
+        }
+
+        if let Some(status) = fields.status {
+            let (r#type, scheduled) = status.columns();
+            active.r#type = Set(r#type);
+            active.scheduled = Set(scheduled);
+        }
+
+        if let Some(created) = fields.created {
+            active.created = Set(created);
+        }
+
+        if let Some(author_id) = fields.author_id {
+            active.author_id = Set(Some(author_id));
+        }
+
+        active.updated = Set(fields.updated.unwrap_or_else(|| Some(timestamp())));
+
+        let txn = self.db.begin().await?;
+
+        if changed {
+            revision.insert(&txn).await?;
+        }
+
+        let article = active.update(&txn).await?;
+
+        if let Some(tags) = fields.tags {
+            set_tags(&txn, article.article_id, tags).await?;
+        }
+
+        txn.commit().await?;
+
+        // The url might have changed, so both entries are stale
+        self.remove(url);
+        self.remove(&article.url);
+
+        info!("Updated article {}", article.url);
+
+        Ok(Some(article))
+    }
+
+    pub async fn delete_article(&self, url: &str) -> Result<bool> {
+        let article = ArticleEntity::find()
+            .filter(article::Column::Url.eq(url))
+            .one(&self.db)
+            .await?;
+
+        let article = match article {
+            Some(article) => article,
+            None => {
+                debug!("No article found with url {url}");
+                return Ok(false);
+            }
+        };
+
+        let txn = self.db.begin().await?;
+
+        ArticleTagEntity::delete_many()
+            .filter(article_tag::Column::ArticleId.eq(article.article_id))
+            .exec(&txn)
+            .await?;
+
+        ArticleRevisionEntity::delete_many()
+            .filter(article_revision::Column::ArticleId.eq(article.article_id))
+            .exec(&txn)
+            .await?;
+
+        ArticleEntity::delete_by_id(article.article_id)
+            .exec(&txn)
+            .await?;
+
+        txn.commit().await?;
+
+        self.remove(url);
+
+        info!("Deleted article {url}");
+
+        Ok(true)
+    }
+
+    // Revisions of an article, newest first
+    pub async fn get_revisions(&self, url: &str) -> Result<Option<Vec<Revision>>> {
+        let article = ArticleEntity::find()
+            .filter(article::Column::Url.eq(url))
+            .one(&self.db)
+            .await?;
+
+        let article = match article {
+            Some(article) => article,
+            None => {
+                debug!("No article found with url {url}");
+                return Ok(None);
+            }
+        };
+
+        let revisions = ArticleRevisionEntity::find()
+            .filter(article_revision::Column::ArticleId.eq(article.article_id))
+            .order_by_desc(article_revision::Column::RevisionId)
+            .all(&self.db)
+            .await?;
+
+        Ok(Some(revisions))
+    }
+
+    pub async fn get_revision(&self, url: &str, revision_id: i64) -> Result<Option<Revision>> {
+        let revision = ArticleRevisionEntity::find_by_id(revision_id)
+            .find_also_related(ArticleEntity)
+            .one(&self.db)
+            .await?;
+
+        // The id has to belong to the article in the url
+        match revision {
+            Some((revision, Some(article))) if article.url == url => Ok(Some(revision)),
+            _ => {
+                debug!("No revision {revision_id} found for article {url}");
+                Ok(None)
+            }
+        }
+    }
+
+    // Goes through `update_article`, so the current state becomes a revision itself
+    pub async fn restore_revision(&self, url: &str, revision_id: i64) -> Result<Option<Article>> {
+        let revision = match self.get_revision(url, revision_id).await? {
+            Some(revision) => revision,
+            None => return Ok(None),
+        };
+
+        let fields = ArticleFields {
+            title: Some(revision.title),
+            markdown: Some(revision.markdown),
+            excerpt: Some(revision.excerpt),
+            ..Default::default()
+        };
+
+        let article = self.update_article(url, fields).await?;
+
+        info!("Restored article {url} to revision {revision_id}");
+
+        Ok(article)
+    }
+
+    // Articles of a deleted user go back to the site's author
+    pub async fn remove_author(&self, user_id: i64) -> Result<()> {
+        ArticleEntity::update_many()
+            .col_expr(article::Column::AuthorId, Expr::value(Option::<i64>::None))
+            .filter(article::Column::AuthorId.eq(user_id))
+            .exec(&self.db)
+            .await?;
+
+        self.cache.write().clear();
+
+        Ok(())
+    }
+
+    // Regenerate `content` for every article, used when the rendering pipeline changes
+    pub async fn rerender_all(&self) -> Result<usize> {
+        let articles = ArticleEntity::find().all(&self.db).await?;
+        let mut count = 0;
+
+        for article in articles {
+            let content = markdown::render(&article.markdown);
+            if content == article.content {
+                continue;
+            }
+
+            let url = article.url.clone();
+
+            // This is not an edit, so `updated` is left alone
+            let mut active: article::ActiveModel = article.into();
+            active.content = Set(content);
+            active.update(&self.db).await?;
+
+            self.remove(&url);
+            count += 1;
+
+            debug!("Re-rendered article {url}");
+        }
+
+        info!("Re-rendered {count} article(s)");
+
+        Ok(count)
+    }
+
+    async fn url_exists(&self, url: &str) -> Result<bool> {
+        let article = ArticleEntity::find()
+            .filter(article::Column::Url.eq(url))
+            .one(&self.db)
+            .await?;
+
+        Ok(article.is_some())
+    }
+
+    // Posts and pages in any state, oldest first
+    pub async fn get_all_articles(&self) -> Result<Vec<Article>> {
+        let articles = ArticleEntity::find()
+            .order_by_asc(article::Column::Created)
+            .order_by_asc(article::Column::ArticleId)
+            .all(&self.db)
+            .await?;
+
+        Ok(articles)
+    }
+
+    pub async fn get_article_tags(&self, article_id: i64) -> Result<Vec<String>> {
+        let mut tags: Vec<String> = TagEntity::find()
+            .inner_join(ArticleTagEntity)
+            .filter(article_tag::Column::ArticleId.eq(article_id))
             .all(&self.db)
             .await?
             .into_iter()
-            .map(|article| article.into())
+            .filter_map(|tag| tag.tag)
             .collect();
 
-        articles.sort_by(|a, b| {
-            b.updated
-                .unwrap_or(b.created)
-                .cmp(&a.updated.unwrap_or(a.created))
-        });
+        tags.sort();
+
+        Ok(tags)
+    }
+
+    // Tags in use by articles the audience can see, sorted by name
+    pub async fn get_tags(&self, audience: Audience) -> Result<Vec<TagCount>> {
+        let mut counts: HashMap<String, usize> = HashMap::new();
+
+        for metadata in self.get_all_article_metadatas(audience).await? {
+            for tag in metadata.tags {
+                *counts.entry(tag).or_insert(0) += 1;
+            }
+        }
+
+        let mut tags: Vec<_> = counts
+            .into_iter()
+            .map(|(tag, count)| TagCount { tag, count })
+            .collect();
+
+        tags.sort_by(|a, b| a.tag.cmp(&b.tag));
+
+        Ok(tags)
+    }
+
+    pub async fn get_article_metadatas_by_tag(
+        &self,
+        tag: &str,
+        audience: Audience,
+    ) -> Result<Vec<ArticleMetadata>> {
+        // TODO: could be optimized
+        let metadata = self
+            .get_all_article_metadatas(audience)
+            .await?
+            .into_iter()
+            .filter(|article| article.tags.iter().any(|t| t == tag))
+            .collect();
+
+        Ok(metadata)
+    }
+
+    async fn get_article_ids_by_tag(&self, tag: &str) -> Result<Vec<i64>> {
+        let ids = ArticleTagEntity::find()
+            .inner_join(TagEntity)
+            .filter(tag::Column::Tag.eq(tag))
+            .all(&self.db)
+            .await?
+            .into_iter()
+            .filter_map(|article_tag| article_tag.article_id)
+            .collect();
+
+        Ok(ids)
+    }
+
+    async fn get_all_tags_by_article(&self) -> Result<HashMap<i64, Vec<String>>> {
+        let rows = ArticleTagEntity::find()
+            .find_also_related(TagEntity)
+            .all(&self.db)
+            .await?;
+
+        let mut tags: HashMap<i64, Vec<String>> = HashMap::new();
+
+        for (article_tag, tag) in rows {
+            let (article_id, tag) = match (article_tag.article_id, tag.and_then(|t| t.tag)) {
+                (Some(article_id), Some(tag)) => (article_id, tag),
+                _ => continue,
+            };
+
+            tags.entry(article_id).or_default().push(tag);
+        }
+
+        for tags in tags.values_mut() {
+            tags.sort();
+        }
+
+        Ok(tags)
+    }
+
+    // Blog posts, pages are left out
+    pub async fn get_all_article_metadatas(
+        &self,
+        audience: Audience,
+    ) -> Result<Vec<ArticleMetadata>> {
+        self.get_metadatas(ArticleKind::Post, audience).await
+    }
+
+    // Newest first by when they went out, so editing an old post does not bring it back up
+    pub async fn get_latest_articles(
+        &self,
+        audience: Audience,
+        limit: usize,
+    ) -> Result<Vec<Article>> {
+        let mut articles: Vec<Article> = ArticleEntity::find()
+            .filter(article::Column::Kind.eq(ArticleKind::Post.column()))
+            .all(&self.db)
+            .await?;
+        articles.retain(|article| audience.can_list(article));
+
+        articles.sort_by_key(|article| std::cmp::Reverse(published_at(article)));
+        articles.truncate(limit);
+
+        Ok(articles)
+    }
+
+    pub async fn get_page_metadatas(&self, audience: Audience) -> Result<Vec<ArticleMetadata>> {
+        self.get_metadatas(ArticleKind::Page, audience).await
+    }
+
+    async fn get_metadatas(
+        &self,
+        kind: ArticleKind,
+        audience: Audience,
+    ) -> Result<Vec<ArticleMetadata>> {
+        let mut articles: Vec<Article> = ArticleEntity::find()
+            .filter(article::Column::Kind.eq(kind.column()))
+            .all(&self.db)
+            .await?;
+        articles.retain(|article| audience.can_list(article));
+        let mut tags = self.get_all_tags_by_article().await?;
 
         // sort by update time
-        let mut articles = articles;
-        articles.sort_by(|a, b| {
-            b.updated
-                .unwrap_or(b.created)
-                .cmp(&a.updated.unwrap_or(a.created))
-        });
+        articles.sort_by_key(|article| std::cmp::Reverse(last_modified(article)));
 
         let metadata: Vec<ArticleMetadata> = articles
             .into_iter()
             .map(|article| {
+                let last_modified = last_modified(&article);
+
                 let year = {
-                    let timestamp = article.updated.unwrap_or(article.created);
+                    let timestamp = last_modified;
                     Local
                         .timestamp_opt(timestamp, 0)
                         .map(|dt| dt.year())
@@ -133,6 +900,8 @@ impl ArticleManager {
                     updated: article.updated.map(|t| format!("{}", t)),
                     url: article.url,
                     year,
+                    tags: tags.remove(&article.article_id).unwrap_or_default(),
+                    last_modified,
                 }
             })
             .collect();
@@ -140,3 +909,145 @@ impl ArticleManager {
         Ok(metadata)
     }
 }
+
+fn check_page_url(kind: ArticleKind, url: &str) -> Result<()> {
+    if kind == ArticleKind::Page && RESERVED_PAGE_URLS.contains(&url) {
+        return Err(invalid(format!(
+            "url {url} is reserved and cannot be used by a page"
+        )));
+    }
+
+    Ok(())
+}
+
+// Where `term` starts in `text`, ignoring case like the index does
+fn find_term(text: &[char], term: &[char]) -> Vec<usize> {
+    if term.is_empty() || term.len() > text.len() {
+        return vec![];
+    }
+
+    (0..=text.len() - term.len())
+        .filter(|&start| {
+            text[start..start + term.len()]
+                .iter()
+                .zip(term)
+                .all(|(a, b)| a.to_lowercase().eq(b.to_lowercase()))
+        })
+        .collect()
+}
+
+fn count_term(text: &str, term: &[char]) -> usize {
+    let text: Vec<char> = text.chars().collect();
+    find_term(&text, term).len()
+}
+
+// Around the first match, about as long as the ones FTS5 makes
+fn snippet_of(text: &[char], terms: &[Vec<char>]) -> Vec<char> {
+    const BEFORE: usize = 8;
+    const LENGTH: usize = 32;
+
+    let first = terms
+        .iter()
+        .filter_map(|term| find_term(text, term).first().copied())
+        .min()
+        .unwrap_or(0);
+
+    let start = first.saturating_sub(BEFORE);
+    let end = (start + LENGTH).min(text.len());
+
+    let mut snippet = Vec::new();
+    if start > 0 {
+        snippet.push('…');
+    }
+    snippet.extend_from_slice(&text[start..end]);
+    if end < text.len() {
+        snippet.push('…');
+    }
+
+    snippet
+}
+
+// HTML with `terms` marked as well as whatever was marked already
+fn render_snippet(snippet: &[char], terms: &[Vec<char>]) -> String {
+    let mut marked = vec![false; snippet.len()];
+    for term in terms {
+        for start in find_term(snippet, term) {
+            marked[start..start + term.len()].fill(true);
+        }
+    }
+
+    let mut text = String::new();
+    for (i, c) in snippet.iter().enumerate() {
+        if marked[i] && (i == 0 || !marked[i - 1]) {
+            text.push_str(MARK_START);
+        }
+
+        text.push(*c);
+
+        if marked[i] && (i + 1 == snippet.len() || !marked[i + 1]) {
+            text.push_str(MARK_END);
+        }
+    }
+
+    escape_html(&text)
+        .replace(MARK_START, "<mark>")
+        .replace(MARK_END, "</mark>")
+}
+
+// When the article went out, a scheduled article counts from its schedule
+pub fn published_at(article: &Article) -> i64 {
+    article.scheduled.unwrap_or(article.created)
+}
+
+fn last_modified(article: &Article) -> i64 {
+    article
+        .updated
+        .unwrap_or(article.created)
+        .max(published_at(article))
+}
+
+// Replace the tags of an article, creating tags that do not exist yet
+async fn set_tags<C: ConnectionTrait>(conn: &C, article_id: i64, tags: Vec<String>) -> Result<()> {
+    ArticleTagEntity::delete_many()
+        .filter(article_tag::Column::ArticleId.eq(article_id))
+        .exec(conn)
+        .await?;
+
+    let mut seen = vec![];
+
+    for tag in tags {
+        let tag = tag.trim().to_owned();
+        if tag.is_empty() || seen.contains(&tag) {
+            continue;
+        }
+
+        let existing = TagEntity::find()
+            .filter(tag::Column::Tag.eq(&tag))
+            .one(conn)
+            .await?;
+
+        let tag_id = match existing {
+            Some(existing) => existing.tag_id,
+            None => {
+                let new_tag = tag::ActiveModel {
+                    tag: Set(Some(tag.clone())),
+                    ..Default::default()
+                };
+
+                new_tag.insert(conn).await?.tag_id
+            }
+        };
+
+        let article_tag = article_tag::ActiveModel {
+            article_id: Set(Some(article_id)),
+            tag_id: Set(Some(tag_id)),
+            ..Default::default()
+        };
+
+        article_tag.insert(conn).await?;
+
+        seen.push(tag);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,147 @@
+use std::net::IpAddr;
+
+use anyhow::Result;
+use log::{debug, info};
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, Condition, DatabaseConnection, EntityTrait, QueryFilter,
+    QueryOrder, Set, TransactionTrait,
+};
+
+use entity::{
+    ip_ban::{self, Model as IpBan},
+    prelude::IpBan as IpBanEntity,
+};
+
+use crate::util::*;
+
+const DEFAULT_MAX_ATTEMPTS: u32 = 5;
+const DEFAULT_BAN_DURATION: i64 = 60 * 60 * 12; // 12 hours
+
+// Failed logins per address, and the addresses banned for having too many. Kept in the
+// database so a restart is no way around a ban.
+pub struct BanManager {
+    db: DatabaseConnection,
+    max_attempts: u32,
+    ban_duration: i64,
+}
+
+impl BanManager {
+    pub fn new(
+        db: DatabaseConnection,
+        max_attempts: Option<u32>,
+        ban_duration: Option<i64>,
+    ) -> Self {
+        Self {
+            db,
+            max_attempts: max_attempts.unwrap_or(DEFAULT_MAX_ATTEMPTS).max(1),
+            ban_duration: ban_duration.unwrap_or(DEFAULT_BAN_DURATION).max(1),
+        }
+    }
+
+    // When the ban on the address ends, if there is one
+    pub async fn banned_until(&self, ip: &IpAddr) -> Result<Option<i64>> {
+        let entry = IpBanEntity::find_by_id(ip.to_string())
+            .one(&self.db)
+            .await?;
+
+        Ok(entry
+            .and_then(|entry| entry.banned_until)
+            .filter(|until| *until > timestamp()))
+    }
+
+    pub async fn record_failure(&self, ip: &IpAddr) -> Result<()> {
+        let now = timestamp();
+        let txn = self.db.begin().await?;
+
+        let entry = IpBanEntity::find_by_id(ip.to_string()).one(&txn).await?;
+
+        // Failures count for as long as a ban would last, older ones are forgotten
+        let failures = match &entry {
+            Some(entry) if entry.last_failure + self.ban_duration > now => entry.failures + 1,
+            _ => 1,
+        };
+
+        let banned_until = match failures as u32 >= self.max_attempts {
+            true => {
+                info!("Banning {ip} after {failures} failed login attempt(s)");
+                Some(now + self.ban_duration)
+            }
+            false => None,
+        };
+
+        let active = ip_ban::ActiveModel {
+            ip: Set(ip.to_string()),
+            failures: Set(failures),
+            last_failure: Set(now),
+            banned_until: Set(banned_until),
+        };
+
+        match entry {
+            Some(_) => {
+                active.update(&txn).await?;
+            }
+            // The ip is not an auto increment column, so there is no id to read back
+            None => {
+                IpBanEntity::insert(active)
+                    .exec_without_returning(&txn)
+                    .await?;
+            }
+        }
+
+        txn.commit().await?;
+
+        Ok(())
+    }
+
+    pub async fn record_success(&self, ip: &IpAddr) -> Result<()> {
+        IpBanEntity::delete_by_id(ip.to_string())
+            .exec(&self.db)
+            .await?;
+
+        Ok(())
+    }
+
+    // Addresses banned right now, the ones banned longest first
+    pub async fn get_bans(&self) -> Result<Vec<IpBan>> {
+        let bans = IpBanEntity::find()
+            .filter(ip_ban::Column::BannedUntil.gt(timestamp()))
+            .order_by_asc(ip_ban::Column::BannedUntil)
+            .all(&self.db)
+            .await?;
+
+        Ok(bans)
+    }
+
+    // Forgets the failures of the address as well, so it starts over
+    pub async fn lift_ban(&self, ip: &str) -> Result<bool> {
+        let result = IpBanEntity::delete_by_id(ip).exec(&self.db).await?;
+
+        if result.rows_affected > 0 {
+            info!("Lifted ban on {ip}");
+        }
+
+        Ok(result.rows_affected > 0)
+    }
+
+    // Bans that ran out, and failures too old to count
+    pub async fn purge_expired(&self) -> Result<u64> {
+        let now = timestamp();
+
+        let result = IpBanEntity::delete_many()
+            .filter(
+                Condition::any()
+                    .add(ip_ban::Column::BannedUntil.lte(now))
+                    .add(
+                        Condition::all()
+                            .add(ip_ban::Column::BannedUntil.is_null())
+                            .add(ip_ban::Column::LastFailure.lte(now - self.ban_duration)),
+                    ),
+            )
+            .exec(&self.db)
+            .await?;
+
+        debug!("Purged {} expired login failure(s)", result.rows_affected);
+
+        Ok(result.rows_affected)
+    }
+}
@@ -1,21 +1,76 @@
-use std::net::SocketAddr;
+use std::net::{IpAddr, SocketAddr};
 
-use anyhow::Result;
-use clap::{ArgAction, Parser};
-use serde::Deserialize;
+use anyhow::{anyhow, Result};
+use clap::{ArgAction, Parser, Subcommand};
+use serde::{Deserialize, Serialize};
 use tokio::fs::read_to_string;
 
 #[derive(Parser, Debug)]
 #[command(name = env!("CARGO_PKG_NAME"))]
 #[command(version = env!("CARGO_PKG_VERSION"))]
 pub struct Args {
-    /// Path to configuration file
+    /// Path to configuration file, needed by every command but hash-password
     #[arg(short, long, value_name = "/path/to/config.yaml")]
-    config: String,
+    config: Option<String>,
 
     /// Verbose level
     #[arg(short, long, action = ArgAction::Count)]
     pub verbose: u8,
+
+    /// Reload templates and assets from disk whenever they change, and the open pages with them
+    #[arg(long)]
+    pub dev: bool,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Regenerate the rendered HTML of every article from its Markdown
+    Rerender,
+    /// Generate resized and WebP variants of images in the media directory
+    ProcessImages,
+    /// Export the site as static files, or its content as Markdown
+    Export {
+        #[command(subcommand)]
+        target: ExportTarget,
+    },
+    /// Read a password from standard input and print its hash for the config
+    HashPassword,
+    /// Import Markdown files with Hugo or Jekyll style front matter, a JSON archive or a
+    /// WordPress export
+    Import {
+        /// Directory to look for .md files in, subdirectories included, an archive.json or a
+        /// WordPress .xml export
+        #[arg(value_name = "/path/to/posts")]
+        source: String,
+
+        /// Where to write the map from old WordPress permalinks to the new urls, next to the
+        /// export by default
+        #[arg(long, value_name = "/path/to/redirects.yaml")]
+        redirects: Option<String>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ExportTarget {
+    /// Render the whole site into a directory of static files
+    Site {
+        /// Directory to write the site to
+        #[arg(value_name = "/path/to/output")]
+        output: String,
+    },
+    /// Write every article as a Markdown file with front matter, along with the site metadata
+    Markdown {
+        /// Directory to write the files to
+        #[arg(value_name = "/path/to/output")]
+        output: String,
+
+        /// Also write a JSON archive that `import` restores losslessly
+        #[arg(long, value_name = "/path/to/archive.json")]
+        json: Option<String>,
+    },
 }
 
 #[derive(Deserialize)]
@@ -27,18 +82,46 @@ pub enum Locale {
     Zh,
 }
 
+#[derive(Deserialize, Serialize, Clone)]
+pub struct MenuItem {
+    pub title: String,
+    pub url: String,
+}
+
 #[derive(Deserialize)]
 pub struct Config {
     pub database_path: String,
+    pub media_path: Option<String>,
+    pub theme_path: Option<String>,
     pub bind: SocketAddr,
     pub locale: Locale,
+    // Feeds and the sitemap need it for absolute links, they fall back to relative ones
+    pub base_url: Option<String>,
+    pub robots_txt: Option<String>,
+    pub menu: Option<Vec<MenuItem>>,
     pub username: String,
+    // An Argon2 hash from `hash-password`
     pub password: String,
+    pub basic_auth: Option<bool>,
+    pub session_secret: Option<String>,
+    // Failed logins from one address before it is banned, and the ban in seconds
+    pub max_login_attempts: Option<u32>,
+    pub ban_duration: Option<i64>,
+    // Reverse proxies whose X-Forwarded-For header tells the client address
+    pub trusted_proxies: Option<Vec<IpAddr>>,
+    // Set from the command line
+    #[serde(skip)]
+    pub dev: bool,
 }
 
-pub async fn build_config(args: Args) -> Result<Config> {
-    let file = read_to_string(&args.config).await?;
-    let config = serde_yaml::from_str(&file)?;
+pub async fn build_config(args: &Args) -> Result<Config> {
+    let path = args
+        .config
+        .as_ref()
+        .ok_or(anyhow!("--config is required"))?;
+    let file = read_to_string(path).await?;
+    let mut config: Config = serde_yaml::from_str(&file)?;
+    config.dev = args.dev;
 
     Ok(config)
 }
@@ -0,0 +1,253 @@
+use std::path::Path;
+
+use anyhow::Result;
+use chrono::{Local, TimeZone};
+use hashbrown::HashSet;
+use log::info;
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+
+use entity::{article::Model as Article, metadata::Model as Metadata};
+
+use crate::{
+    article_manager::{ArticleKind, ArticleStatus},
+    site::Site,
+    util::*,
+};
+
+// Bumped whenever the meaning of a field in the archive changes
+pub const ARCHIVE_VERSION: u32 = 2;
+
+// Everything needed to rebuild the articles and settings of a site, `content` is left out
+// since it is rendered from the Markdown on import
+#[derive(Serialize, Deserialize)]
+pub struct Archive {
+    pub version: u32,
+    pub metadata: Vec<MetadataEntry>,
+    pub articles: Vec<ArchivedArticle>,
+}
+
+// Mirrors the columns of the metadata table
+#[derive(Serialize, Deserialize)]
+pub struct MetadataEntry {
+    pub key: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub val_int: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub val_float: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub val_string: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ArchivedArticle {
+    pub title: String,
+    pub url: String,
+    pub markdown: String,
+    pub excerpt: String,
+    pub created: i64,
+    pub updated: Option<i64>,
+    pub status: ArchivedStatus,
+    // Only set for scheduled articles
+    pub scheduled: Option<i64>,
+    pub kind: ArchivedKind,
+    pub tags: Vec<String>,
+    // Username of the author, since version 2
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub author: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ArchivedStatus {
+    Published,
+    Draft,
+    Unlisted,
+    Scheduled,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ArchivedKind {
+    Post,
+    Page,
+}
+
+// The keys are the ones `import` reads back
+#[derive(Serialize)]
+struct FrontMatter<'a> {
+    title: &'a str,
+    slug: &'a str,
+    date: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    updated: Option<String>,
+    // Hugo holds back posts with a future publishDate, which is what scheduling does
+    #[serde(rename = "publishDate", skip_serializing_if = "Option::is_none")]
+    publish_date: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tags: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    draft: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    unlisted: Option<bool>,
+    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
+    r#type: Option<&'static str>,
+    // Written even when empty, or import would make one up
+    description: &'a str,
+}
+
+// Writes posts and pages as Markdown files with front matter under `output`, the site
+// metadata next to them, and optionally everything again as a JSON archive
+pub async fn export_markdown(site: &Site, output: &str, json: Option<&str>) -> Result<()> {
+    let output = Path::new(output);
+    fs::create_dir_all(output.join("posts")).await?;
+    fs::create_dir_all(output.join("pages")).await?;
+
+    let mut archive = Archive {
+        version: ARCHIVE_VERSION,
+        metadata: site
+            .get_all_metadata()
+            .await?
+            .into_iter()
+            .map(to_entry)
+            .collect(),
+        articles: vec![],
+    };
+
+    let metadata = serde_yaml::to_string(&archive.metadata)?;
+    fs::write(output.join("metadata.yaml"), metadata).await?;
+
+    // Different urls can end up with the same file name
+    let mut used = HashSet::new();
+
+    for article in site.article_manager.get_all_articles().await? {
+        let tags = site
+            .article_manager
+            .get_article_tags(article.article_id)
+            .await?;
+
+        let dir = match ArticleKind::of(&article) {
+            ArticleKind::Post => "posts",
+            ArticleKind::Page => "pages",
+        };
+
+        let mut name = file_name_of(&article.url);
+        if !used.insert(format!("{dir}/{name}")) {
+            name = format!("{name}-{}", article.article_id);
+        }
+
+        let text = to_markdown(&article, &tags)?;
+        fs::write(output.join(dir).join(format!("{name}.md")), text).await?;
+
+        let author = match article.author_id {
+            Some(author_id) => site.user_manager.get_user(author_id).await?,
+            None => None,
+        };
+
+        archive
+            .articles
+            .push(to_archived(article, tags, author.map(|user| user.username)));
+    }
+
+    info!(
+        "Exported {} article(s) and {} metadata key(s) to {}",
+        archive.articles.len(),
+        archive.metadata.len(),
+        output.display()
+    );
+
+    if let Some(json) = json {
+        fs::write(json, serde_json::to_string_pretty(&archive)?).await?;
+        info!("Wrote archive to {json}");
+    }
+
+    Ok(())
+}
+
+fn to_markdown(article: &Article, tags: &[String]) -> Result<String> {
+    let status = ArticleStatus::of(article);
+
+    let front_matter = FrontMatter {
+        title: &article.title,
+        slug: &article.url,
+        date: format_date(article.created),
+        updated: article.updated.map(format_date),
+        publish_date: match status {
+            ArticleStatus::Scheduled(scheduled) => Some(format_date(scheduled)),
+            _ => None,
+        },
+        tags: tags.to_vec(),
+        draft: (status == ArticleStatus::Draft).then_some(true),
+        unlisted: (status == ArticleStatus::Unlisted).then_some(true),
+        r#type: (ArticleKind::of(article) == ArticleKind::Page).then_some("page"),
+        description: &article.excerpt,
+    };
+
+    let front_matter = serde_yaml::to_string(&front_matter)?;
+
+    Ok(format!("---\n{front_matter}---\n\n{}\n", article.markdown))
+}
+
+fn to_archived(article: Article, tags: Vec<String>, author: Option<String>) -> ArchivedArticle {
+    let (status, scheduled) = match ArticleStatus::of(&article) {
+        ArticleStatus::Published => (ArchivedStatus::Published, None),
+        ArticleStatus::Draft => (ArchivedStatus::Draft, None),
+        ArticleStatus::Unlisted => (ArchivedStatus::Unlisted, None),
+        ArticleStatus::Scheduled(scheduled) => (ArchivedStatus::Scheduled, Some(scheduled)),
+    };
+
+    let kind = match ArticleKind::of(&article) {
+        ArticleKind::Post => ArchivedKind::Post,
+        ArticleKind::Page => ArchivedKind::Page,
+    };
+
+    ArchivedArticle {
+        title: article.title,
+        url: article.url,
+        markdown: article.markdown,
+        excerpt: article.excerpt,
+        created: article.created,
+        updated: article.updated,
+        status,
+        scheduled,
+        kind,
+        tags,
+        author,
+    }
+}
+
+fn to_entry(metadata: Metadata) -> MetadataEntry {
+    MetadataEntry {
+        key: metadata.key,
+        val_int: metadata.val_int,
+        val_float: metadata.val_float,
+        val_string: metadata.val_string,
+    }
+}
+
+// RFC 3339 in local time, which `import` reads back to the same second
+fn format_date(timestamp: i64) -> String {
+    match Local.timestamp_opt(timestamp, 0).single() {
+        Some(dt) => dt.to_rfc3339(),
+        None => timestamp.to_string(),
+    }
+}
+
+// The url is kept in the front matter, so the file name only has to be safe and readable
+fn file_name_of(url: &str) -> String {
+    let name: String = url
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' => '-',
+            c => c,
+        })
+        .collect();
+
+    // Hidden files are skipped by `import`
+    let name = name.trim_start_matches('.');
+
+    match name.is_empty() {
+        true => urlencode(url),
+        false => name.to_owned(),
+    }
+}
@@ -0,0 +1,278 @@
+use std::io::Cursor;
+
+use anyhow::{anyhow, Result};
+use hashbrown::HashMap;
+use image::{
+    codecs::{jpeg::JpegEncoder, webp::WebPEncoder},
+    imageops::FilterType,
+    DynamicImage, ImageDecoder, ImageFormat, ImageReader,
+};
+
+// Widths of the resized variants, only those narrower than the original are made
+const VARIANT_WIDTHS: &[u32] = &[480, 960, 1440];
+
+const JPEG_QUALITY: u8 = 85;
+
+// Matches the width of the content column in style.css
+const SIZES: &str = "(max-width: 1200px) 100vw, 1160px";
+
+pub struct ProcessedImage {
+    pub width: u32,
+    pub height: u32,
+    pub variants: Vec<Variant>,
+}
+
+pub struct Variant {
+    // Appended to the hash of the original, e.g. `-480w.jpg`
+    pub suffix: String,
+    pub width: u32,
+    pub height: u32,
+    pub data: Vec<u8>,
+}
+
+// Dimensions and variants of an image, as recorded in the media_image table
+pub struct ImageInfo {
+    pub width: u32,
+    pub height: u32,
+    // (file name, width), original format and WebP kept apart for <picture>
+    pub variants: Vec<(String, u32)>,
+    pub webp: Vec<(String, u32)>,
+}
+
+// Decodes an image, then makes resized and WebP variants of it
+pub fn process(data: &[u8], ext: &str) -> Result<ProcessedImage> {
+    let format =
+        ImageFormat::from_extension(ext).ok_or(anyhow!("unsupported image format {ext}"))?;
+
+    let mut decoder = ImageReader::with_format(Cursor::new(data), format).into_decoder()?;
+    let orientation = decoder.orientation()?;
+
+    let mut image = DynamicImage::from_decoder(decoder)?;
+    image.apply_orientation(orientation);
+
+    let (width, height) = (image.width(), image.height());
+
+    // Re-encoding would drop the animation
+    if format == ImageFormat::Gif {
+        return Ok(ProcessedImage {
+            width,
+            height,
+            variants: vec![],
+        });
+    }
+
+    let mut variants = vec![];
+    let mut webp_variants = vec![];
+
+    let widths = VARIANT_WIDTHS.iter().copied().filter(|w| *w < width);
+    for variant_width in widths {
+        let resized = image.resize(variant_width, u32::MAX, FilterType::Lanczos3);
+
+        variants.push(Variant {
+            suffix: format!("-{variant_width}w.{ext}"),
+            width: resized.width(),
+            height: resized.height(),
+            data: encode(&resized, format)?,
+        });
+
+        if format != ImageFormat::WebP {
+            webp_variants.push(Variant {
+                suffix: format!("-{variant_width}w.webp"),
+                width: resized.width(),
+                height: resized.height(),
+                data: encode(&resized, ImageFormat::WebP)?,
+            });
+        }
+    }
+
+    // Re-encoding a well compressed PNG, or anything as lossless WebP, can end up bigger than
+    // the original at a fraction of the size, and then the original is the better download
+    variants.retain(|variant| variant.data.len() < data.len());
+
+    if format != ImageFormat::WebP {
+        webp_variants.push(Variant {
+            suffix: format!("-{width}w.webp"),
+            width,
+            height,
+            data: encode(&image, ImageFormat::WebP)?,
+        });
+
+        // The <source> has no original to fall back on for wide screens, so the WebP
+        // variants are all kept or none
+        if webp_variants
+            .iter()
+            .all(|variant| variant.data.len() < data.len())
+        {
+            variants.append(&mut webp_variants);
+        }
+    }
+
+    Ok(ProcessedImage {
+        width,
+        height,
+        variants,
+    })
+}
+
+fn encode(image: &DynamicImage, format: ImageFormat) -> Result<Vec<u8>> {
+    let mut buffer = vec![];
+
+    match format {
+        ImageFormat::Jpeg => {
+            // JPEG has no alpha channel
+            let image = DynamicImage::ImageRgb8(image.to_rgb8());
+            image.write_with_encoder(JpegEncoder::new_with_quality(&mut buffer, JPEG_QUALITY))?;
+        }
+        ImageFormat::WebP => {
+            let image = match image.color().has_alpha() {
+                true => DynamicImage::ImageRgba8(image.to_rgba8()),
+                false => DynamicImage::ImageRgb8(image.to_rgb8()),
+            };
+            image.write_with_encoder(WebPEncoder::new_lossless(&mut buffer))?;
+        }
+        format => image.write_to(&mut Cursor::new(&mut buffer), format)?,
+    }
+
+    Ok(buffer)
+}
+
+// Media files referenced by <img> tags in rendered article HTML
+pub fn find_images(html: &str) -> Vec<String> {
+    img_tags(html)
+        .filter_map(|(start, end)| {
+            let attributes = parse_attributes(&html[start..end]);
+            media_name(attributes.get("src")?).map(str::to_owned)
+        })
+        .collect()
+}
+
+// Adds dimensions, lazy loading and responsive sources to <img> tags of known images
+pub fn rewrite_images(html: &str, images: &HashMap<String, ImageInfo>) -> String {
+    let mut output = String::with_capacity(html.len());
+    let mut last = 0;
+
+    for (start, end) in img_tags(html) {
+        let tag = &html[start..end];
+        let attributes = parse_attributes(tag);
+
+        let info = attributes
+            .get("src")
+            .and_then(|src| media_name(src))
+            .and_then(|name| images.get(name));
+
+        let info = match info {
+            Some(info) => info,
+            None => continue,
+        };
+
+        output.push_str(&html[last..start]);
+        output.push_str(&responsive_tag(&attributes, info));
+        last = end;
+    }
+
+    output.push_str(&html[last..]);
+
+    output
+}
+
+fn responsive_tag(attributes: &HashMap<String, String>, info: &ImageInfo) -> String {
+    let mut img = String::from("<img");
+
+    // Keep the order stable so the output is deterministic
+    let mut names: Vec<_> = attributes.keys().collect();
+    names.sort();
+
+    for name in names {
+        if matches!(
+            name.as_str(),
+            "width" | "height" | "loading" | "srcset" | "sizes"
+        ) {
+            continue;
+        }
+
+        img.push_str(&format!(" {name}=\"{}\"", attributes[name]));
+    }
+
+    img.push_str(&format!(
+        " width=\"{}\" height=\"{}\" loading=\"lazy\"",
+        info.width, info.height
+    ));
+
+    if !info.variants.is_empty() {
+        let src = &attributes["src"];
+        let srcset = srcset(&info.variants, Some((src, info.width)));
+        img.push_str(&format!(" srcset=\"{srcset}\" sizes=\"{SIZES}\""));
+    }
+
+    img.push('>');
+
+    // Without the full width WebP, wide screens would get a variant scaled up
+    if !info.webp.iter().any(|(_, width)| *width >= info.width) {
+        return img;
+    }
+
+    format!(
+        "<picture><source type=\"image/webp\" srcset=\"{}\" sizes=\"{SIZES}\">{img}</picture>",
+        srcset(&info.webp, None)
+    )
+}
+
+fn srcset(variants: &[(String, u32)], original: Option<(&str, u32)>) -> String {
+    let mut sources: Vec<_> = variants
+        .iter()
+        .map(|(name, width)| format!("/media/{name} {width}w"))
+        .collect();
+
+    if let Some((src, width)) = original {
+        sources.push(format!("{src} {width}w"));
+    }
+
+    sources.join(", ")
+}
+
+fn media_name(src: &str) -> Option<&str> {
+    src.strip_prefix("/media/")
+}
+
+// Byte ranges of every <img ...> tag
+fn img_tags(html: &str) -> impl Iterator<Item = (usize, usize)> + '_ {
+    html.match_indices("<img ").filter_map(|(start, _)| {
+        // `>` is not escaped inside attribute values
+        let mut quoted = false;
+
+        for (i, c) in html[start..].char_indices() {
+            match c {
+                '"' => quoted = !quoted,
+                '>' if !quoted => return Some((start, start + i + 1)),
+                _ => {}
+            }
+        }
+
+        None
+    })
+}
+
+// The content was serialized by the sanitizer, so every value is double quoted
+fn parse_attributes(tag: &str) -> HashMap<String, String> {
+    let mut attributes = HashMap::new();
+
+    let mut rest = tag
+        .trim_start_matches("<img")
+        .trim_end_matches('>')
+        .trim_end_matches('/');
+
+    while let Some(eq) = rest.find("=\"") {
+        let name = rest[..eq].trim().to_owned();
+        let value_start = eq + 2;
+
+        let value_end = match rest[value_start..].find('"') {
+            Some(end) => value_start + end,
+            None => break,
+        };
+
+        attributes.insert(name, rest[value_start..value_end].to_owned());
+        rest = &rest[value_end + 1..];
+    }
+
+    attributes
+}
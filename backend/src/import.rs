@@ -0,0 +1,662 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, TimeZone};
+use hashbrown::{HashMap, HashSet};
+use log::{debug, info, warn};
+use serde::Deserialize;
+use tokio::fs;
+
+use entity::metadata::Model as Metadata;
+
+use crate::{
+    article_manager::{ArticleFields, ArticleKind, ArticleManager, ArticleStatus, Audience},
+    export::{Archive, ArchivedArticle, ArchivedKind, ArchivedStatus, ARCHIVE_VERSION},
+    markdown,
+    site::Site,
+    util::*,
+    wxr,
+};
+
+const EXCERPT_LENGTH: usize = 200;
+
+// Front matter keys understood by both Hugo and Jekyll, anything else is ignored
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct FrontMatter {
+    title: Option<String>,
+    date: Option<Date>,
+    #[serde(alias = "lastmod")]
+    updated: Option<Date>,
+    slug: Option<String>,
+    tags: Option<Tags>,
+    draft: bool,
+    // Jekyll's way of saying draft
+    published: Option<bool>,
+    // Hugo holds back posts until then, imported as scheduled
+    #[serde(rename = "publishDate", alias = "publishdate")]
+    publish_date: Option<Date>,
+    unlisted: bool,
+    // `page` makes a standalone page
+    #[serde(rename = "type")]
+    r#type: Option<String>,
+    #[serde(alias = "description", alias = "summary")]
+    excerpt: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Date {
+    Text(String),
+    // TOML has a date type of its own
+    Toml(toml::value::Datetime),
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Tags {
+    List(Vec<String>),
+    // Jekyll accepts a space separated string
+    Text(String),
+}
+
+struct Post {
+    // Where the post came from, for messages
+    source: String,
+    url: String,
+    title: String,
+    markdown: String,
+    excerpt: String,
+    status: ArticleStatus,
+    tags: Vec<String>,
+    created: i64,
+    // `None` when the source does not say, an archive knows when there was no edit
+    updated: Option<Option<i64>>,
+    // `None` keeps the kind of an existing article
+    kind: Option<ArticleKind>,
+    // `None` keeps the author of an existing article
+    author_id: Option<i64>,
+}
+
+enum Outcome {
+    Created,
+    Updated,
+    Unchanged,
+}
+
+// `source` is a JSON archive written by `export`, a WordPress export or a directory of
+// Markdown files
+pub async fn import(site: &Site, source: &str, redirects: Option<&str>) -> Result<()> {
+    let path = Path::new(source);
+
+    let extension = match path.is_file() {
+        true => path.extension().and_then(|ext| ext.to_str()),
+        false => None,
+    };
+
+    match extension {
+        Some("json") => import_archive(site, path).await,
+        Some("xml") => {
+            let redirects = match redirects {
+                Some(redirects) => PathBuf::from(redirects),
+                None => path.with_extension("redirects.yaml"),
+            };
+
+            import_wxr(&site.article_manager, path, &redirects).await
+        }
+        _ => import_markdown(&site.article_manager, path).await,
+    }
+}
+
+// Creates or updates an article for every Markdown file under `source`
+async fn import_markdown(article_manager: &ArticleManager, source: &Path) -> Result<()> {
+    let mut posts = vec![];
+    let mut failed = 0;
+
+    for path in find_markdown_files(source).await? {
+        match read_post(&path).await {
+            Ok(post) => posts.push(post),
+            Err(e) => {
+                warn!("Skipped {}: {e}", path.display());
+                failed += 1;
+            }
+        }
+    }
+
+    import_posts(article_manager, posts, failed).await?;
+
+    Ok(())
+}
+
+// Restores metadata and articles exactly as they were exported
+async fn import_archive(site: &Site, source: &Path) -> Result<()> {
+    let archive: Archive = serde_json::from_str(&fs::read_to_string(source).await?)?;
+
+    if archive.version > ARCHIVE_VERSION {
+        return Err(anyhow!(
+            "archive version {} is newer than the supported {ARCHIVE_VERSION}",
+            archive.version
+        ));
+    }
+
+    let metadata = archive
+        .metadata
+        .into_iter()
+        .map(|entry| Metadata {
+            key: entry.key,
+            val_int: entry.val_int,
+            val_float: entry.val_float,
+            val_string: entry.val_string,
+        })
+        .collect();
+
+    site.set_metadata(metadata).await?;
+
+    let source = source.display();
+    let mut posts = vec![];
+    let mut failed = 0;
+
+    for (i, article) in archive.articles.into_iter().enumerate() {
+        // An author missing from this site leaves the article to its owner
+        let author_id = match &article.author {
+            Some(username) => match site.user_manager.get_user_by_name(username).await? {
+                Some(user) => Some(user.user_id),
+                None => {
+                    warn!("No user {username} for article {}", article.url);
+                    None
+                }
+            },
+            None => None,
+        };
+
+        match from_archived(article, format!("{source}#{i}"), author_id) {
+            Ok(post) => posts.push(post),
+            Err(e) => {
+                warn!("Skipped article {i} of {source}: {e}");
+                failed += 1;
+            }
+        }
+    }
+
+    import_posts(&site.article_manager, posts, failed).await?;
+
+    Ok(())
+}
+
+// Posts and pages of a WordPress export, along with a map from the old permalinks to the new
+// urls for the web server in front of us
+async fn import_wxr(
+    article_manager: &ArticleManager,
+    source: &Path,
+    redirects: &Path,
+) -> Result<()> {
+    let items = wxr::parse(&fs::read_to_string(source).await?)?;
+
+    let mut posts = vec![];
+    let mut links = vec![];
+    let mut failed = 0;
+
+    for item in items {
+        let kind = match item.post_type.as_str() {
+            "post" => ArticleKind::Post,
+            "page" => ArticleKind::Page,
+            // Attachments, menu items, reusable blocks...
+            _ => continue,
+        };
+
+        let created = match item.created {
+            Some(created) => created,
+            None => {
+                warn!("Skipped WordPress post {}: it has no date", item.id);
+                failed += 1;
+                continue;
+            }
+        };
+
+        let status = match item.status.as_str() {
+            "publish" => ArticleStatus::Published,
+            "future" => ArticleStatus::Scheduled(created),
+            // Private posts were only for logged in users, which we do not have
+            "draft" | "pending" | "private" => ArticleStatus::Draft,
+            // Trash and auto drafts
+            _ => continue,
+        };
+
+        let url = match item.slug.is_empty() {
+            true => format!("wp-{}", item.id),
+            false => urldecode(&item.slug).unwrap_or(item.slug),
+        };
+
+        let excerpt = match item.excerpt.is_empty() {
+            true => {
+                let content = item.content.split("<!--more-->").next().unwrap_or_default();
+                markdown::excerpt(&wxr::strip_tags(content), EXCERPT_LENGTH)
+            }
+            false => item.excerpt,
+        };
+
+        // Drafts never had a public permalink
+        if status != ArticleStatus::Draft {
+            let path = match kind {
+                ArticleKind::Post => format!("/article/{}", urlencode(&url)),
+                ArticleKind::Page => format!("/{}", urlencode(&url)),
+            };
+
+            links.push((url.clone(), [item.link, item.guid], path));
+        }
+
+        posts.push(Post {
+            source: format!("{}#{}", source.display(), item.id),
+            title: item.title,
+            url,
+            // WordPress content is HTML, which Markdown passes through
+            markdown: item.content.trim().to_owned(),
+            excerpt,
+            status,
+            tags: item.tags,
+            created,
+            updated: item.updated.map(Some),
+            kind: Some(kind),
+            author_id: None,
+        });
+    }
+
+    let imported = import_posts(article_manager, posts, failed).await?;
+
+    let mut map = BTreeMap::new();
+    for (url, old_links, path) in links {
+        if !imported.contains(&url) {
+            continue;
+        }
+
+        for old_path in old_links.iter().filter_map(|link| path_of(link)) {
+            if old_path != path {
+                map.insert(old_path, path.clone());
+            }
+        }
+    }
+
+    fs::write(redirects, serde_yaml::to_string(&map)?).await?;
+
+    info!("Wrote {} redirect(s) to {}", map.len(), redirects.display());
+
+    Ok(())
+}
+
+// Returns the urls that now hold the imported content
+async fn import_posts(
+    article_manager: &ArticleManager,
+    posts: Vec<Post>,
+    mut failed: usize,
+) -> Result<HashSet<String>> {
+    // Urls are unique, and picking a winner would depend on the order of the walk
+    let mut sources: HashMap<String, Vec<String>> = HashMap::new();
+    for post in &posts {
+        sources
+            .entry(post.url.clone())
+            .or_default()
+            .push(post.source.clone());
+    }
+
+    let mut counts = (0, 0, 0);
+    let mut imported = HashSet::new();
+
+    for post in posts {
+        let conflicting = &sources[&post.url];
+        if conflicting.len() > 1 {
+            // Reported once per url
+            if conflicting[0] == post.source {
+                warn!(
+                    "Conflicting url {} in {}, none of them were imported",
+                    post.url,
+                    conflicting.join(", ")
+                );
+            }
+
+            failed += 1;
+            continue;
+        }
+
+        let url = post.url.clone();
+
+        match import_post(article_manager, post).await {
+            Ok(outcome) => {
+                match outcome {
+                    Outcome::Created => counts.0 += 1,
+                    Outcome::Updated => counts.1 += 1,
+                    Outcome::Unchanged => counts.2 += 1,
+                }
+
+                imported.insert(url);
+            }
+            Err(e) => {
+                warn!("{e}");
+                failed += 1;
+            }
+        }
+    }
+
+    let (created, updated, unchanged) = counts;
+    info!(
+        "Imported {created} new and {updated} updated article(s), \
+         {unchanged} unchanged, {failed} skipped"
+    );
+
+    Ok(imported)
+}
+
+async fn import_post(article_manager: &ArticleManager, post: Post) -> Result<Outcome> {
+    let existing = article_manager
+        .get_article(&post.url, Audience::Editor)
+        .await?;
+
+    if let Some(existing) = &existing {
+        let mut tags = article_manager
+            .get_article_tags(existing.article_id)
+            .await?;
+        tags.sort();
+
+        let mut new_tags = post.tags.clone();
+        new_tags.sort();
+        new_tags.dedup();
+
+        let unchanged = existing.title == post.title
+            && existing.markdown == post.markdown
+            && existing.excerpt == post.excerpt
+            && existing.created == post.created
+            && post
+                .updated
+                .is_none_or(|updated| existing.updated == updated)
+            && ArticleStatus::of(existing) == post.status
+            && post
+                .kind
+                .is_none_or(|kind| ArticleKind::of(existing) == kind)
+            && post
+                .author_id
+                .is_none_or(|author_id| existing.author_id == Some(author_id))
+            && tags == new_tags;
+
+        if unchanged {
+            debug!("Article {} is up to date", post.url);
+            return Ok(Outcome::Unchanged);
+        }
+    }
+
+    let source = post.source;
+
+    let fields = ArticleFields {
+        title: Some(post.title),
+        url: Some(post.url.clone()),
+        markdown: Some(post.markdown),
+        excerpt: Some(post.excerpt),
+        status: Some(post.status),
+        tags: Some(post.tags),
+        created: Some(post.created),
+        updated: post.updated,
+        kind: post.kind,
+        // Imported articles keep the author they have, new ones get the site's, unless the
+        // archive names one
+        author_id: post.author_id,
+    };
+
+    let outcome = match existing {
+        Some(_) => {
+            article_manager
+                .update_article(&post.url, fields)
+                .await
+                .map_err(|e| anyhow!("Failed to update {} from {source}: {e}", post.url))?;
+            Outcome::Updated
+        }
+        None => {
+            article_manager
+                .create_article(fields)
+                .await
+                .map_err(|e| anyhow!("Failed to create {} from {source}: {e}", post.url))?;
+            Outcome::Created
+        }
+    };
+
+    Ok(outcome)
+}
+
+// Sorted, so runs over the same directory behave the same
+async fn find_markdown_files(source: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = vec![];
+    let mut dirs = vec![source.to_path_buf()];
+
+    while let Some(dir) = dirs.pop() {
+        let mut entries = fs::read_dir(&dir).await?;
+
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            let name = entry.file_name().to_string_lossy().into_owned();
+
+            // Hidden files, and Hugo's _index.md which describes a section rather than a post
+            if name.starts_with('.') || name.starts_with("_index.") {
+                continue;
+            }
+
+            if entry.file_type().await?.is_dir() {
+                dirs.push(path);
+            } else if name.ends_with(".md") || name.ends_with(".markdown") {
+                files.push(path);
+            }
+        }
+    }
+
+    files.sort();
+
+    Ok(files)
+}
+
+async fn read_post(path: &Path) -> Result<Post> {
+    let text = fs::read_to_string(path).await?;
+    let text = text.trim_start_matches('\u{feff}');
+
+    let (front_matter, body) = match split_front_matter(text, "---") {
+        Some((yaml, body)) => (serde_yaml::from_str(yaml)?, body),
+        None => match split_front_matter(text, "+++") {
+            Some((toml, body)) => (toml::from_str(toml)?, body),
+            None => (FrontMatter::default(), text),
+        },
+    };
+
+    let (file_date, file_slug) = slug_of(path)?;
+
+    let url = front_matter.slug.unwrap_or(file_slug);
+    if url.is_empty() || url.contains('/') {
+        return Err(anyhow!("invalid url {url:?}"));
+    }
+
+    let created = match (front_matter.date, file_date) {
+        (Some(date), _) => parse_date(&date)?,
+        (None, Some(date)) => parse_date(&Date::Text(date))?,
+        (None, None) => return Err(anyhow!("no date in front matter or file name")),
+    };
+
+    let updated = front_matter
+        .updated
+        .map(|date| parse_date(&date))
+        .transpose()?;
+
+    let markdown = body.trim().to_owned();
+
+    let excerpt = match front_matter.excerpt {
+        Some(excerpt) => excerpt.trim().to_owned(),
+        None => markdown::excerpt(&markdown, EXCERPT_LENGTH),
+    };
+
+    let publish_date = front_matter
+        .publish_date
+        .map(|date| parse_date(&date))
+        .transpose()?;
+
+    let status = match (front_matter.draft, front_matter.published) {
+        (true, _) | (_, Some(false)) => ArticleStatus::Draft,
+        _ if front_matter.unlisted => ArticleStatus::Unlisted,
+        _ => match publish_date {
+            Some(publish_date) => ArticleStatus::Scheduled(publish_date),
+            None if created > timestamp() => ArticleStatus::Scheduled(created),
+            None => ArticleStatus::Published,
+        },
+    };
+
+    let kind = front_matter.r#type.map(|r#type| match r#type.as_str() {
+        "page" => ArticleKind::Page,
+        _ => ArticleKind::Post,
+    });
+
+    let tags = match front_matter.tags {
+        Some(Tags::List(tags)) => tags,
+        Some(Tags::Text(tags)) => tags.split_whitespace().map(str::to_owned).collect(),
+        None => vec![],
+    };
+
+    let tags = tags
+        .iter()
+        .map(|tag| tag.trim().to_owned())
+        .filter(|tag| !tag.is_empty())
+        .collect();
+
+    Ok(Post {
+        source: path.display().to_string(),
+        title: front_matter.title.unwrap_or_else(|| url.clone()),
+        url,
+        markdown,
+        excerpt,
+        status,
+        tags,
+        created,
+        updated: updated.map(Some),
+        kind,
+        author_id: None,
+    })
+}
+
+fn from_archived(article: ArchivedArticle, source: String, author_id: Option<i64>) -> Result<Post> {
+    let status = match (article.status, article.scheduled) {
+        (ArchivedStatus::Published, _) => ArticleStatus::Published,
+        (ArchivedStatus::Draft, _) => ArticleStatus::Draft,
+        (ArchivedStatus::Unlisted, _) => ArticleStatus::Unlisted,
+        (ArchivedStatus::Scheduled, Some(scheduled)) => ArticleStatus::Scheduled(scheduled),
+        (ArchivedStatus::Scheduled, None) => {
+            return Err(anyhow!("scheduled article {} has no time", article.url))
+        }
+    };
+
+    let kind = match article.kind {
+        ArchivedKind::Post => ArticleKind::Post,
+        ArchivedKind::Page => ArticleKind::Page,
+    };
+
+    Ok(Post {
+        source,
+        url: article.url,
+        title: article.title,
+        markdown: article.markdown,
+        excerpt: article.excerpt,
+        status,
+        tags: article.tags,
+        created: article.created,
+        updated: Some(article.updated),
+        kind: Some(kind),
+        author_id,
+    })
+}
+
+// Returns the front matter between the delimiter lines and the body after them
+fn split_front_matter<'a>(text: &'a str, delimiter: &str) -> Option<(&'a str, &'a str)> {
+    let rest = text.strip_prefix(delimiter)?;
+    let rest = rest
+        .strip_prefix("\r\n")
+        .or_else(|| rest.strip_prefix('\n'))?;
+
+    let mut offset = 0;
+
+    for line in rest.split_inclusive('\n') {
+        if line.trim_end() == delimiter {
+            return Some((&rest[..offset], &rest[offset + line.len()..]));
+        }
+
+        offset += line.len();
+    }
+
+    None
+}
+
+// Hugo bundles are `<slug>/index.md`, Jekyll posts are `YYYY-MM-DD-<slug>.md`
+fn slug_of(path: &Path) -> Result<(Option<String>, String)> {
+    let stem = path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .ok_or(anyhow!("file name is not valid UTF-8"))?;
+
+    let stem = match stem {
+        "index" => path
+            .parent()
+            .and_then(|parent| parent.file_name())
+            .and_then(|name| name.to_str())
+            .ok_or(anyhow!("bundle directory name is not valid UTF-8"))?,
+        stem => stem,
+    };
+
+    let dated = stem.get(..11).filter(|prefix| {
+        prefix.ends_with('-') && NaiveDate::parse_from_str(&prefix[..10], "%Y-%m-%d").is_ok()
+    });
+
+    match dated {
+        Some(prefix) => Ok((Some(prefix[..10].to_owned()), stem[11..].to_owned())),
+        None => Ok((None, stem.to_owned())),
+    }
+}
+
+// Dates without an offset are taken as local time, like the editor does
+fn parse_date(date: &Date) -> Result<i64> {
+    let date = match date {
+        Date::Text(text) => text.trim().to_owned(),
+        Date::Toml(datetime) => datetime.to_string(),
+    };
+
+    if let Ok(dt) = DateTime::parse_from_rfc3339(&date) {
+        return Ok(dt.timestamp());
+    }
+
+    // Jekyll writes `2024-01-31 10:00:00 +0800`
+    if let Ok(dt) = DateTime::parse_from_str(&date, "%Y-%m-%d %H:%M:%S %z") {
+        return Ok(dt.timestamp());
+    }
+
+    let naive = [
+        "%Y-%m-%dT%H:%M:%S%.f",
+        "%Y-%m-%d %H:%M:%S%.f",
+        "%Y-%m-%d %H:%M",
+    ]
+    .iter()
+    .find_map(|format| NaiveDateTime::parse_from_str(&date, format).ok())
+    .or_else(|| {
+        NaiveDate::parse_from_str(&date, "%Y-%m-%d")
+            .ok()
+            .and_then(|date| date.and_hms_opt(0, 0, 0))
+    });
+
+    naive
+        .and_then(|naive| Local.from_local_datetime(&naive).earliest())
+        .map(|dt| dt.timestamp())
+        .ok_or(anyhow!("unrecognized date {date}"))
+}
+
+// Path and query of an old permalink, e.g. `/2019/05/hello/` or `/?p=12`
+fn path_of(link: &str) -> Option<String> {
+    let path = match link.split_once("://") {
+        Some((_, rest)) => &rest[rest.find('/')?..],
+        None => link,
+    };
+
+    match path.starts_with('/') && path != "/" {
+        true => Some(path.to_owned()),
+        false => None,
+    }
+}
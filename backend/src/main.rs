@@ -1,20 +1,43 @@
-#![feature(try_blocks)]
-
 mod article_manager;
+mod ban_manager;
 mod config;
 mod db;
+mod export;
+mod image_pipeline;
+mod import;
 mod logging;
+mod markdown;
+mod media_manager;
+mod password;
 mod server;
+mod session_manager;
 mod site;
 mod text;
+mod theme;
+mod token_manager;
+mod totp;
+mod two_factor;
+mod user_manager;
 mod util;
+mod wxr;
 
-use std::process::exit;
+use std::{
+    io::{stdin, IsTerminal},
+    process::exit,
+};
 
 use clap::Parser;
 use log::{error, info};
 
-use crate::{config::Args, logging::setup_logger, server::serve, site::Site};
+use crate::{
+    config::{Args, Command, ExportTarget},
+    export::export_markdown,
+    import::import,
+    logging::setup_logger,
+    password::hash_password,
+    server::{export_site, serve},
+    site::Site,
+};
 
 #[tokio::main]
 async fn main() {
@@ -25,11 +48,34 @@ async fn main() {
         exit(1);
     }
 
-    let config = match config::build_config(args).await {
+    // Needs no config or database, so before either is loaded
+    if let Some(Command::HashPassword) = args.command {
+        if stdin().is_terminal() {
+            eprintln!("Password:");
+        }
+
+        let mut password = String::new();
+        if let Err(e) = stdin().read_line(&mut password) {
+            error!("Failed to read password: {e}");
+            exit(1);
+        }
+
+        match hash_password(password.trim_end_matches(['\r', '\n'])) {
+            Ok(hash) => println!("{hash}"),
+            Err(e) => {
+                error!("Failed to hash password: {e}");
+                exit(1);
+            }
+        }
+
+        return;
+    }
+
+    let config = match config::build_config(&args).await {
         Ok(config) => config,
         Err(e) => {
             error!("Failed to build config: {e}");
-            return;
+            exit(1);
         }
     };
 
@@ -37,10 +83,54 @@ async fn main() {
         Ok(site) => site,
         Err(e) => {
             error!("Failed to load site: {e}");
-            return;
+            exit(1);
         }
     };
 
+    match args.command {
+        Some(Command::Rerender) => {
+            if let Err(e) = site.article_manager.rerender_all().await {
+                error!("Failed to re-render articles: {e}");
+                exit(1);
+            }
+
+            return;
+        }
+        Some(Command::ProcessImages) => {
+            if let Err(e) = site.media_manager.process_all().await {
+                error!("Failed to process images: {e}");
+                exit(1);
+            }
+
+            return;
+        }
+        Some(Command::Export { target }) => {
+            let result = match target {
+                ExportTarget::Site { output } => export_site(site, &output).await,
+                ExportTarget::Markdown { output, json } => {
+                    export_markdown(&site, &output, json.as_deref()).await
+                }
+            };
+
+            if let Err(e) = result {
+                error!("Failed to export: {e}");
+                exit(1);
+            }
+
+            return;
+        }
+        Some(Command::Import { source, redirects }) => {
+            if let Err(e) = import(&site, &source, redirects.as_deref()).await {
+                error!("Failed to import articles: {e}");
+                exit(1);
+            }
+
+            return;
+        }
+        Some(Command::HashPassword) => unreachable!(),
+        None => {}
+    }
+
     info!(
         "Started {} version {}",
         env!("CARGO_PKG_NAME"),
@@ -49,6 +139,6 @@ async fn main() {
 
     if let Err(e) = serve(config.bind, site).await {
         error!("Failed to start server: {e}");
-        return;
+        exit(1);
     }
 }
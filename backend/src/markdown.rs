@@ -0,0 +1,160 @@
+use std::borrow::Cow;
+
+use ammonia::Builder;
+use hashbrown::HashMap;
+use once_cell::sync::Lazy;
+use pulldown_cmark::{html, CowStr, Event, Options, Parser, Tag, TagEnd};
+
+// Renders article Markdown into the HTML stored in the `content` column
+pub fn render(markdown: &str) -> String {
+    let parser = Parser::new_ext(markdown, options());
+    let events = add_heading_anchors(parser.collect());
+
+    let mut html_output = String::new();
+    html::push_html(&mut html_output, events.into_iter());
+
+    sanitize(&html_output)
+}
+
+// Plain text excerpt, everything before `<!--more-->` or else the first paragraph
+pub fn excerpt(markdown: &str, max_chars: usize) -> String {
+    let (head, has_marker) = match markdown.split_once("<!--more-->") {
+        Some((head, _)) => (head, true),
+        None => (markdown, false),
+    };
+
+    let mut text = String::new();
+    let mut in_paragraph = false;
+
+    for event in Parser::new_ext(head, options()) {
+        match event {
+            Event::Start(Tag::Paragraph) => in_paragraph = true,
+            Event::End(TagEnd::Paragraph) => {
+                in_paragraph = false;
+
+                if !has_marker && !text.is_empty() {
+                    break;
+                }
+
+                text.push(' ');
+            }
+            Event::Text(t) | Event::Code(t) if in_paragraph => text.push_str(&t),
+            Event::SoftBreak | Event::HardBreak if in_paragraph => text.push(' '),
+            _ => {}
+        }
+    }
+
+    let text = text.trim();
+
+    match text.char_indices().nth(max_chars) {
+        Some((end, _)) => format!("{}…", text[..end].trim_end()),
+        None => text.to_owned(),
+    }
+}
+
+fn options() -> Options {
+    Options::ENABLE_TABLES
+        | Options::ENABLE_FOOTNOTES
+        | Options::ENABLE_STRIKETHROUGH
+        | Options::ENABLE_TASKLISTS
+        | Options::ENABLE_HEADING_ATTRIBUTES
+}
+
+// Give every heading without an explicit `{#id}` an id derived from its text
+fn add_heading_anchors(mut events: Vec<Event>) -> Vec<Event> {
+    let mut used: HashMap<String, usize> = HashMap::new();
+
+    for i in 0..events.len() {
+        let explicit = match &events[i] {
+            Event::Start(Tag::Heading { id, .. }) => id.as_ref().map(|id| id.to_string()),
+            _ => continue,
+        };
+
+        if let Some(id) = explicit {
+            used.entry(id).or_insert(0);
+            continue;
+        }
+
+        let text: String = events[i + 1..]
+            .iter()
+            .take_while(|e| !matches!(e, Event::End(TagEnd::Heading(_))))
+            .filter_map(|e| match e {
+                Event::Text(text) | Event::Code(text) => Some(text.as_ref()),
+                _ => None,
+            })
+            .collect();
+
+        let slug = slugify(&text);
+        let slug = match used.get_mut(&slug) {
+            Some(count) => {
+                *count += 1;
+                format!("{slug}-{count}")
+            }
+            None => slug,
+        };
+
+        used.insert(slug.clone(), 0);
+
+        if let Event::Start(Tag::Heading { id, .. }) = &mut events[i] {
+            *id = Some(CowStr::from(slug));
+        }
+    }
+
+    events
+}
+
+fn slugify(text: &str) -> String {
+    let mut slug = String::new();
+
+    for c in text.trim().chars() {
+        if c.is_alphanumeric() {
+            slug.extend(c.to_lowercase());
+        } else if (c.is_whitespace() || c == '-' || c == '_') && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+
+    let slug = slug.trim_matches('-');
+
+    match slug.is_empty() {
+        true => "section".to_owned(),
+        false => slug.to_owned(),
+    }
+}
+
+fn sanitize(html: &str) -> String {
+    static SANITIZER: Lazy<Builder<'static>> = Lazy::new(|| {
+        let mut builder = Builder::default();
+
+        builder
+            .add_tags(&["input"])
+            .add_tag_attributes("input", &["type", "checked", "disabled"])
+            .add_tag_attributes("th", &["style"])
+            .add_tag_attributes("td", &["style"])
+            .add_tag_attributes("code", &["class"])
+            .add_tag_attributes("sup", &["class"])
+            .add_tag_attributes("div", &["class", "id"])
+            .attribute_filter(|element, attribute, value| match (element, attribute) {
+                // Only the column alignment emitted by the renderer is allowed
+                ("th" | "td", "style") => match value {
+                    "text-align: left" | "text-align: center" | "text-align: right" => {
+                        Some(Cow::Borrowed(value))
+                    }
+                    _ => None,
+                },
+                ("input", "type") => match value {
+                    "checkbox" => Some(Cow::Borrowed(value)),
+                    _ => None,
+                },
+                _ => Some(Cow::Borrowed(value)),
+            });
+
+        for heading in ["h1", "h2", "h3", "h4", "h5", "h6"] {
+            builder.add_tag_attributes(heading, &["id"]);
+        }
+
+        builder
+    });
+
+    SANITIZER.clean(html).to_string()
+}
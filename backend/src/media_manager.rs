@@ -0,0 +1,342 @@
+use std::{
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Result};
+use hashbrown::HashMap;
+use log::{debug, info, warn};
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, Set,
+    TransactionTrait,
+};
+use sha2::{Digest, Sha256};
+use tokio::{fs, task::spawn_blocking};
+
+use entity::{media_image, prelude::MediaImage as MediaImageEntity};
+
+use crate::{
+    image_pipeline::{self, ImageInfo},
+    util::*,
+};
+
+// Cheap to clone, for jobs that outlive the request
+#[derive(Clone)]
+pub struct MediaManager {
+    path: PathBuf,
+    db: DatabaseConnection,
+}
+
+pub struct MediaFile {
+    // File name under the media directory, also used in the url
+    pub name: String,
+    pub mime_type: &'static str,
+    pub size: u64,
+    pub created: i64,
+}
+
+impl MediaManager {
+    pub fn new(path: &str, db: DatabaseConnection) -> Result<Self> {
+        let path = PathBuf::from(path);
+        std::fs::create_dir_all(&path)?;
+
+        Ok(Self { path, db })
+    }
+
+    // Files are named after their content, so uploading the same file twice is harmless
+    pub async fn store(&self, original_name: &str, data: &[u8]) -> Result<MediaFile> {
+        let mime_type = mime_type_of(original_name)
+            .ok_or(invalid(format!("unsupported file type of {original_name}")))?;
+
+        let (_, ext) = original_name
+            .rsplit_once('.')
+            .ok_or(invalid(format!("file {original_name} has no extension")))?;
+
+        let hash = format!("{:x}", Sha256::digest(data));
+        let name = format!("{}.{}", &hash[..32], ext.to_lowercase());
+        let path = self.path.join(&name);
+
+        if fs::try_exists(&path).await? {
+            debug!("Media {name} already exists");
+        } else {
+            // Write to a temporary file first so readers never see a partial file
+            let temp = self.path.join(format!(".{name}.tmp"));
+            fs::write(&temp, data).await?;
+            fs::rename(&temp, &path).await?;
+
+            info!("Stored media {name} from {original_name}");
+
+            // Resizing takes seconds, so the upload does not wait for it. Articles pick the
+            // variants up once they are there, and `process_all` catches anything a restart
+            // interrupted.
+            let manager = self.clone();
+            let (name, data) = (name.clone(), data.to_vec());
+            tokio::spawn(async move {
+                // A broken image is still a valid upload, it just goes out as is
+                if let Err(e) = manager.process_image(&name, data).await {
+                    warn!("Failed to process image {name}: {e}");
+                }
+            });
+        }
+
+        let metadata = fs::metadata(&path).await?;
+
+        Ok(MediaFile {
+            name,
+            mime_type,
+            size: metadata.len(),
+            created: created_of(&metadata),
+        })
+    }
+
+    pub async fn load(&self, name: &str) -> Result<Option<(Vec<u8>, &'static str)>> {
+        let path = match self.resolve(name) {
+            Some(path) => path,
+            None => return Ok(None),
+        };
+
+        let mime_type = match mime_type_of(name) {
+            Some(mime_type) => mime_type,
+            None => return Ok(None),
+        };
+
+        match fs::read(&path).await {
+            Ok(data) => Ok(Some((data, mime_type))),
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                debug!("No media found with name {name}");
+                Ok(None)
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    // Newest first
+    pub async fn list(&self) -> Result<Vec<MediaFile>> {
+        let mut files = vec![];
+        let mut entries = fs::read_dir(&self.path).await?;
+
+        while let Some(entry) = entries.next_entry().await? {
+            // Variants are found through their original
+            let name = match entry.file_name().into_string() {
+                Ok(name) if is_original_name(&name) => name,
+                _ => continue,
+            };
+
+            let mime_type = match mime_type_of(&name) {
+                Some(mime_type) => mime_type,
+                None => continue,
+            };
+
+            let metadata = entry.metadata().await?;
+
+            files.push(MediaFile {
+                name,
+                mime_type,
+                size: metadata.len(),
+                created: created_of(&metadata),
+            });
+        }
+
+        files.sort_by(|a, b| b.created.cmp(&a.created).then(a.name.cmp(&b.name)));
+
+        Ok(files)
+    }
+
+    // Copies every media file, variants included, into `target`
+    pub async fn copy_all(&self, target: &Path) -> Result<usize> {
+        fs::create_dir_all(target).await?;
+
+        let mut count = 0;
+        let mut entries = fs::read_dir(&self.path).await?;
+
+        while let Some(entry) = entries.next_entry().await? {
+            // Skips temporary files of uploads in progress
+            let name = match entry.file_name().into_string() {
+                Ok(name) if is_media_name(&name) => name,
+                _ => continue,
+            };
+
+            fs::copy(entry.path(), target.join(&name)).await?;
+            count += 1;
+        }
+
+        Ok(count)
+    }
+
+    // Generate variants for every original image that has not been processed yet
+    pub async fn process_all(&self) -> Result<usize> {
+        let mut count = 0;
+
+        for file in self.list().await? {
+            let processed = MediaImageEntity::find_by_id(file.name.clone())
+                .one(&self.db)
+                .await?
+                .is_some();
+
+            if processed {
+                continue;
+            }
+
+            let data = fs::read(self.path.join(&file.name)).await?;
+
+            match self.process_image(&file.name, data).await {
+                Ok(true) => count += 1,
+                Ok(false) => {}
+                Err(e) => warn!("Failed to process image {}: {e}", file.name),
+            }
+        }
+
+        info!("Processed {count} image(s)");
+
+        Ok(count)
+    }
+
+    // Returns whether the file was an image that could be processed
+    async fn process_image(&self, name: &str, data: Vec<u8>) -> Result<bool> {
+        let (hash, ext) = name
+            .split_once('.')
+            .ok_or(anyhow!("file {name} has no extension"))?;
+
+        // Vector images need neither resizing nor dimensions
+        if !mime_type_of(name).is_some_and(|mime_type| mime_type.starts_with("image/"))
+            || ext == "svg"
+        {
+            return Ok(false);
+        }
+
+        let ext = ext.to_owned();
+        let processed = spawn_blocking(move || image_pipeline::process(&data, &ext)).await??;
+
+        let mut rows = vec![(name.to_owned(), processed.width, processed.height)];
+
+        for variant in processed.variants {
+            let file = format!("{hash}{}", variant.suffix);
+            fs::write(self.path.join(&file), &variant.data).await?;
+            rows.push((file, variant.width, variant.height));
+        }
+
+        let txn = self.db.begin().await?;
+
+        MediaImageEntity::delete_many()
+            .filter(media_image::Column::Source.eq(name))
+            .exec(&txn)
+            .await?;
+
+        for (file, width, height) in rows {
+            let image = media_image::ActiveModel {
+                file: Set(file),
+                source: Set(name.to_owned()),
+                width: Set(width as i32),
+                height: Set(height as i32),
+            };
+
+            image.insert(&txn).await?;
+        }
+
+        txn.commit().await?;
+
+        debug!("Processed image {name}");
+
+        Ok(true)
+    }
+
+    // Dimensions and variants of the given originals, unknown names are left out
+    pub async fn get_images(&self, names: &[String]) -> Result<HashMap<String, ImageInfo>> {
+        if names.is_empty() {
+            return Ok(HashMap::new());
+        }
+
+        let rows = MediaImageEntity::find()
+            .filter(media_image::Column::Source.is_in(names.iter().cloned()))
+            .all(&self.db)
+            .await?;
+
+        let mut images: HashMap<String, ImageInfo> = HashMap::new();
+
+        // Originals first, so variants always find their entry
+        let (originals, variants): (Vec<_>, Vec<_>) =
+            rows.into_iter().partition(|row| row.file == row.source);
+
+        for row in originals {
+            let info = ImageInfo {
+                width: row.width as u32,
+                height: row.height as u32,
+                variants: vec![],
+                webp: vec![],
+            };
+
+            images.insert(row.source, info);
+        }
+
+        for row in variants {
+            let info = match images.get_mut(&row.source) {
+                Some(info) => info,
+                None => continue,
+            };
+
+            let entry = (row.file, row.width as u32);
+
+            match entry.0.ends_with(".webp") && !row.source.ends_with(".webp") {
+                true => info.webp.push(entry),
+                false => info.variants.push(entry),
+            }
+        }
+
+        for info in images.values_mut() {
+            info.variants.sort_by_key(|(_, width)| *width);
+            info.webp.sort_by_key(|(_, width)| *width);
+        }
+
+        Ok(images)
+    }
+
+    // Rendered HTML with dimensions and responsive sources on images we know about
+    pub async fn responsive_html(&self, html: &str) -> Result<String> {
+        let names = image_pipeline::find_images(html);
+        let images = self.get_images(&names).await?;
+
+        Ok(image_pipeline::rewrite_images(html, &images))
+    }
+
+    // Only names produced by `store` are accepted, which keeps requests inside the directory
+    fn resolve(&self, name: &str) -> Option<PathBuf> {
+        match is_media_name(name) {
+            true => Some(self.path.join(Path::new(name))),
+            false => None,
+        }
+    }
+}
+
+// `<hash>.<ext>` for originals, `<hash>-<width>w.<ext>` for variants
+fn is_media_name(name: &str) -> bool {
+    let (stem, ext) = match name.split_once('.') {
+        Some(parts) => parts,
+        None => return false,
+    };
+
+    let hash = match stem.split_once('-') {
+        Some((hash, width)) => match width.strip_suffix('w') {
+            Some(width) if !width.is_empty() && width.bytes().all(|b| b.is_ascii_digit()) => hash,
+            _ => return false,
+        },
+        None => stem,
+    };
+
+    !hash.is_empty()
+        && hash.bytes().all(|b| b.is_ascii_hexdigit())
+        && !ext.is_empty()
+        && ext.bytes().all(|b| b.is_ascii_alphanumeric())
+}
+
+fn is_original_name(name: &str) -> bool {
+    is_media_name(name) && !name.contains('-')
+}
+
+fn created_of(metadata: &std::fs::Metadata) -> i64 {
+    metadata
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(std::time::UNIX_EPOCH).ok())
+        .map(|duration| duration.as_secs() as i64)
+        .unwrap_or_else(timestamp)
+}
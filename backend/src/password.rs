@@ -0,0 +1,36 @@
+use anyhow::{anyhow, Result};
+use argon2::{
+    password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+    Argon2,
+};
+
+// A PHC string like `$argon2id$v=19$m=19456,t=2,p=1$<salt>$<hash>`
+pub fn hash_password(password: &str) -> Result<String> {
+    let salt = SaltString::generate(&mut OsRng);
+
+    let hash = Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map_err(|e| anyhow!("failed to hash password: {e}"))?;
+
+    Ok(hash.to_string())
+}
+
+// Older configs have the password in plain text, which still works until it is replaced
+pub fn is_hashed(stored: &str) -> bool {
+    PasswordHash::new(stored).is_ok()
+}
+
+// Slow on purpose, call it from a blocking task
+pub fn verify_password(stored: &str, password: &str) -> bool {
+    match PasswordHash::new(stored) {
+        Ok(hash) => Argon2::default()
+            .verify_password(password.as_bytes(), &hash)
+            .is_ok(),
+        Err(_) => constant_time_eq(stored.as_bytes(), password.as_bytes()),
+    }
+}
+
+// Does not stop at the first difference, so timing tells nothing about the secret
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
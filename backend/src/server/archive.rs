@@ -9,7 +9,7 @@ use axum::{
 use log::warn;
 use serde::Serialize;
 
-use crate::{site::Site, util::*};
+use crate::{article_manager::Audience, site::Site, util::*};
 
 pub async fn archive_handler(Extension(site): Extension<Arc<Site>>) -> impl IntoResponse {
     let text = match archive_impl(site).await {
@@ -26,7 +26,7 @@ pub async fn archive_handler(Extension(site): Extension<Arc<Site>>) -> impl Into
     (StatusCode::OK, headers, text).into_response()
 }
 
-async fn archive_impl(site: Arc<Site>) -> Result<String> {
+pub async fn archive_impl(site: Arc<Site>) -> Result<String> {
     // This object should be as simple as possible to increase serialization speed
     #[derive(Serialize)]
     struct Article {
@@ -48,7 +48,7 @@ async fn archive_impl(site: Arc<Site>) -> Result<String> {
     // TODO: could be optimized
     let metadata: Vec<_> = site
         .article_manager
-        .get_all_article_metadatas()
+        .get_all_article_metadatas(Audience::Public)
         .await?
         .into_iter()
         .collect();
@@ -4,18 +4,44 @@ use anyhow::Result;
 use axum::{
     extract::Path,
     http::{header, StatusCode},
-    response::IntoResponse,
+    response::{IntoResponse, Response},
     Extension,
 };
 use log::warn;
 
-use crate::{site::Site, util::*};
+use entity::{article::Model as Article, user::Model as User};
+
+use crate::{
+    article_manager::{published_at, ArticleKind, Audience},
+    server::{auth::authorize_article, page::render_page},
+    site::Site,
+    util::*,
+};
 
 pub async fn article_handler(
     Extension(site): Extension<Arc<Site>>,
     Path(url): Path<String>,
 ) -> impl IntoResponse {
-    let article = match article_impl(site, &url).await {
+    article_response(site, &url, Audience::Public).await
+}
+
+// Lets the editor look at drafts and scheduled articles before they go out
+pub async fn article_preview_handler(
+    Extension(site): Extension<Arc<Site>>,
+    Extension(user): Extension<User>,
+    Path(url): Path<String>,
+) -> Response {
+    if let Some(response) = authorize_article(&site, &user, &url).await {
+        return response;
+    }
+
+    article_response(site, &url, Audience::Editor)
+        .await
+        .into_response()
+}
+
+async fn article_response(site: Arc<Site>, url: &str, audience: Audience) -> impl IntoResponse {
+    let article = match article_impl(site, url, audience).await {
         Ok(Some(article)) => article,
         Ok(None) => return StatusCode::NOT_FOUND.into_response(),
         Err(e) => {
@@ -29,26 +55,47 @@ pub async fn article_handler(
     (StatusCode::OK, headers, article).into_response()
 }
 
-async fn article_impl(site: Arc<Site>, url: &str) -> Result<Option<String>> {
-    let article = site.article_manager.get_article(url).await?;
+pub async fn article_impl(
+    site: Arc<Site>,
+    url: &str,
+    audience: Audience,
+) -> Result<Option<String>> {
+    let article = site.article_manager.get_article(url, audience).await?;
     let article = match article {
         Some(article) => article,
         None => return Ok(None),
     };
 
-    let author = site.get_author();
+    // Pages live at the top level, the preview shows them as they will look there
+    let rendered = match (ArticleKind::of(&article), audience) {
+        (ArticleKind::Post, _) => render_article(&site, article).await?,
+        (ArticleKind::Page, Audience::Editor) => render_page(&site, article).await?,
+        (ArticleKind::Page, Audience::Public) => return Ok(None),
+    };
+
+    Ok(Some(rendered))
+}
+
+async fn render_article(site: &Site, article: Article) -> Result<String> {
+    let tags = site
+        .article_manager
+        .get_article_tags(article.article_id)
+        .await?;
+
+    let author = site.get_article_author(&article).await?;
+    let created = site.format_time(published_at(&article));
     let title = article.title;
-    let content = article.content;
-    let created = site.format_time(article.created);
+    let content = site.media_manager.responsive_html(&article.content).await?;
 
     let mut context = site.base_context();
     context.insert("title", &title);
     context.insert("author", &author);
     context.insert("content", &content);
     context.insert("created", &created);
+    context.insert("tags", &tags);
 
     let rendered = site.render("article.html", &context)?;
     let minified = minify_html(rendered)?;
 
-    Ok(Some(minified))
+    Ok(minified)
 }
@@ -0,0 +1,450 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use axum::{
+    body::Bytes,
+    extract::{Path, Query},
+    http::StatusCode,
+    response::IntoResponse,
+    Extension,
+};
+use log::warn;
+use serde::Deserialize;
+use similar::{ChangeTag, TextDiff};
+
+use entity::{article::Model, article_revision::Model as RevisionModel, user::Model as User};
+
+use crate::{
+    article_manager::{ArticleFields, ArticleKind, ArticleStatus, Audience},
+    server::auth::authorize_article,
+    site::Site,
+    util::*,
+};
+
+mod proto {
+    include!(concat!(env!("OUT_DIR"), "/blog.article.rs"));
+}
+
+use proto::{diff_line::Op, Article, Diff, DiffLine, Kind, Revision, Revisions, Status, Tags};
+
+#[derive(Deserialize)]
+pub struct DiffQuery {
+    from: i64,
+    // The current version of the article if omitted
+    to: Option<i64>,
+}
+
+pub async fn article_fetch_handler(
+    Extension(site): Extension<Arc<Site>>,
+    Extension(user): Extension<User>,
+    Path(url): Path<String>,
+) -> impl IntoResponse {
+    if let Some(response) = authorize_article(&site, &user, &url).await {
+        return response;
+    }
+
+    let response = match article_fetch_impl(site, &url).await {
+        Ok(Some(response)) => response,
+        Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+        Err(e) => {
+            warn!("Failed to fetch article {url}: {e}");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    let headers = [("Content-Type", "application/octet-stream")];
+    (StatusCode::OK, headers, response).into_response()
+}
+
+pub async fn article_create_handler(
+    Extension(site): Extension<Arc<Site>>,
+    Extension(user): Extension<User>,
+    body: Bytes,
+) -> impl IntoResponse {
+    let response = match article_create_impl(site, &user, body.to_vec()).await {
+        Ok(response) => response,
+        Err(e) => {
+            warn!("Failed to create article: {e}");
+            return error_status(&e).into_response();
+        }
+    };
+
+    let headers = [("Content-Type", "application/octet-stream")];
+    (StatusCode::CREATED, headers, response).into_response()
+}
+
+pub async fn article_update_handler(
+    Extension(site): Extension<Arc<Site>>,
+    Extension(user): Extension<User>,
+    Path(url): Path<String>,
+    body: Bytes,
+) -> impl IntoResponse {
+    if let Some(response) = authorize_article(&site, &user, &url).await {
+        return response;
+    }
+
+    let response = match article_update_impl(site, &url, body.to_vec()).await {
+        Ok(Some(response)) => response,
+        Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+        Err(e) => {
+            warn!("Failed to update article {url}: {e}");
+            return error_status(&e).into_response();
+        }
+    };
+
+    let headers = [("Content-Type", "application/octet-stream")];
+    (StatusCode::OK, headers, response).into_response()
+}
+
+pub async fn article_delete_handler(
+    Extension(site): Extension<Arc<Site>>,
+    Extension(user): Extension<User>,
+    Path(url): Path<String>,
+) -> impl IntoResponse {
+    if let Some(response) = authorize_article(&site, &user, &url).await {
+        return response;
+    }
+
+    match site.article_manager.delete_article(&url).await {
+        Ok(true) => StatusCode::NO_CONTENT.into_response(),
+        Ok(false) => StatusCode::NOT_FOUND.into_response(),
+        Err(e) => {
+            warn!("Failed to delete article {url}: {e}");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+pub async fn revision_list_handler(
+    Extension(site): Extension<Arc<Site>>,
+    Extension(user): Extension<User>,
+    Path(url): Path<String>,
+) -> impl IntoResponse {
+    if let Some(response) = authorize_article(&site, &user, &url).await {
+        return response;
+    }
+
+    let response = match revision_list_impl(site, &url).await {
+        Ok(Some(response)) => response,
+        Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+        Err(e) => {
+            warn!("Failed to list revisions of article {url}: {e}");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    let headers = [("Content-Type", "application/octet-stream")];
+    (StatusCode::OK, headers, response).into_response()
+}
+
+pub async fn revision_fetch_handler(
+    Extension(site): Extension<Arc<Site>>,
+    Extension(user): Extension<User>,
+    Path((url, revision_id)): Path<(String, i64)>,
+) -> impl IntoResponse {
+    if let Some(response) = authorize_article(&site, &user, &url).await {
+        return response;
+    }
+
+    let response = match revision_fetch_impl(site, &url, revision_id).await {
+        Ok(Some(response)) => response,
+        Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+        Err(e) => {
+            warn!("Failed to fetch revision {revision_id} of article {url}: {e}");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    let headers = [("Content-Type", "application/octet-stream")];
+    (StatusCode::OK, headers, response).into_response()
+}
+
+pub async fn revision_diff_handler(
+    Extension(site): Extension<Arc<Site>>,
+    Extension(user): Extension<User>,
+    Path(url): Path<String>,
+    Query(query): Query<DiffQuery>,
+) -> impl IntoResponse {
+    if let Some(response) = authorize_article(&site, &user, &url).await {
+        return response;
+    }
+
+    let response = match revision_diff_impl(site, &url, query).await {
+        Ok(Some(response)) => response,
+        Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+        Err(e) => {
+            warn!("Failed to diff revisions of article {url}: {e}");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    let headers = [("Content-Type", "application/octet-stream")];
+    (StatusCode::OK, headers, response).into_response()
+}
+
+pub async fn revision_restore_handler(
+    Extension(site): Extension<Arc<Site>>,
+    Extension(user): Extension<User>,
+    Path((url, revision_id)): Path<(String, i64)>,
+) -> impl IntoResponse {
+    if let Some(response) = authorize_article(&site, &user, &url).await {
+        return response;
+    }
+
+    let response = match revision_restore_impl(site, &url, revision_id).await {
+        Ok(Some(response)) => response,
+        Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+        Err(e) => {
+            warn!("Failed to restore revision {revision_id} of article {url}: {e}");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    let headers = [("Content-Type", "application/octet-stream")];
+    (StatusCode::OK, headers, response).into_response()
+}
+
+async fn article_fetch_impl(site: Arc<Site>, url: &str) -> Result<Option<Vec<u8>>> {
+    let article = match site
+        .article_manager
+        .get_article(url, Audience::Editor)
+        .await?
+    {
+        Some(article) => article,
+        None => return Ok(None),
+    };
+
+    let tags = site
+        .article_manager
+        .get_article_tags(article.article_id)
+        .await?;
+
+    let response = protobuf_encode(to_proto(article, tags))?;
+
+    Ok(Some(response))
+}
+
+async fn article_create_impl(site: Arc<Site>, user: &User, body: Vec<u8>) -> Result<Vec<u8>> {
+    let request: Article = protobuf_decode(&body)?;
+
+    let fields = ArticleFields {
+        author_id: Some(user.user_id),
+        ..to_fields(request)
+    };
+
+    let article = site.article_manager.create_article(fields).await?;
+
+    let tags = site
+        .article_manager
+        .get_article_tags(article.article_id)
+        .await?;
+
+    let response = protobuf_encode(to_proto(article, tags))?;
+
+    Ok(response)
+}
+
+async fn article_update_impl(site: Arc<Site>, url: &str, body: Vec<u8>) -> Result<Option<Vec<u8>>> {
+    let request: Article = protobuf_decode(&body)?;
+
+    let article = site
+        .article_manager
+        .update_article(url, to_fields(request))
+        .await?;
+
+    let article = match article {
+        Some(article) => article,
+        None => return Ok(None),
+    };
+
+    let tags = site
+        .article_manager
+        .get_article_tags(article.article_id)
+        .await?;
+
+    let response = protobuf_encode(to_proto(article, tags))?;
+
+    Ok(Some(response))
+}
+
+async fn revision_list_impl(site: Arc<Site>, url: &str) -> Result<Option<Vec<u8>>> {
+    let revisions = match site.article_manager.get_revisions(url).await? {
+        Some(revisions) => revisions,
+        None => return Ok(None),
+    };
+
+    // Markdown is only sent for single revisions to keep the list small
+    let revisions = revisions
+        .into_iter()
+        .map(|revision| Revision {
+            markdown: None,
+            ..revision_to_proto(revision)
+        })
+        .collect();
+
+    let response = protobuf_encode(Revisions { revisions })?;
+
+    Ok(Some(response))
+}
+
+async fn revision_fetch_impl(
+    site: Arc<Site>,
+    url: &str,
+    revision_id: i64,
+) -> Result<Option<Vec<u8>>> {
+    let revision = match site.article_manager.get_revision(url, revision_id).await? {
+        Some(revision) => revision,
+        None => return Ok(None),
+    };
+
+    let response = protobuf_encode(revision_to_proto(revision))?;
+
+    Ok(Some(response))
+}
+
+async fn revision_diff_impl(
+    site: Arc<Site>,
+    url: &str,
+    query: DiffQuery,
+) -> Result<Option<Vec<u8>>> {
+    let old = match site.article_manager.get_revision(url, query.from).await? {
+        Some(revision) => revision.markdown,
+        None => return Ok(None),
+    };
+
+    let new = match query.to {
+        Some(to) => site
+            .article_manager
+            .get_revision(url, to)
+            .await?
+            .map(|revision| revision.markdown),
+        None => site
+            .article_manager
+            .get_article(url, Audience::Editor)
+            .await?
+            .map(|article| article.markdown),
+    };
+
+    let new = match new {
+        Some(new) => new,
+        None => return Ok(None),
+    };
+
+    let lines = TextDiff::from_lines(&old, &new)
+        .iter_all_changes()
+        .map(|change| {
+            let op = match change.tag() {
+                ChangeTag::Equal => Op::Equal,
+                ChangeTag::Insert => Op::Insert,
+                ChangeTag::Delete => Op::Delete,
+            };
+
+            DiffLine {
+                op: op.into(),
+                text: change.value().trim_end_matches(['\r', '\n']).to_owned(),
+            }
+        })
+        .collect();
+
+    let response = protobuf_encode(Diff { lines })?;
+
+    Ok(Some(response))
+}
+
+async fn revision_restore_impl(
+    site: Arc<Site>,
+    url: &str,
+    revision_id: i64,
+) -> Result<Option<Vec<u8>>> {
+    let article = site
+        .article_manager
+        .restore_revision(url, revision_id)
+        .await?;
+
+    let article = match article {
+        Some(article) => article,
+        None => return Ok(None),
+    };
+
+    let tags = site
+        .article_manager
+        .get_article_tags(article.article_id)
+        .await?;
+
+    let response = protobuf_encode(to_proto(article, tags))?;
+
+    Ok(Some(response))
+}
+
+fn to_fields(article: Article) -> ArticleFields {
+    // An empty url means "keep the current one" when updating
+    let url = match article.url.is_empty() {
+        true => None,
+        false => Some(article.url),
+    };
+
+    let status = article.status.map(|status| match Status::try_from(status) {
+        Ok(Status::Published) => ArticleStatus::Published,
+        Ok(Status::Unlisted) => ArticleStatus::Unlisted,
+        Ok(Status::Scheduled) => match article.scheduled {
+            Some(scheduled) => ArticleStatus::Scheduled(scheduled),
+            None => ArticleStatus::Draft,
+        },
+        // Unknown values are kept away from the public
+        Ok(Status::Draft) | Err(_) => ArticleStatus::Draft,
+    });
+
+    let kind = article.kind.map(|kind| match Kind::try_from(kind) {
+        Ok(Kind::Page) => ArticleKind::Page,
+        Ok(Kind::Post) | Err(_) => ArticleKind::Post,
+    });
+
+    ArticleFields {
+        title: article.title,
+        url,
+        markdown: article.markdown,
+        excerpt: article.excerpt,
+        status,
+        kind,
+        tags: article.tags.map(|tags| tags.tags),
+        ..Default::default()
+    }
+}
+
+fn to_proto(article: Model, tags: Vec<String>) -> Article {
+    let (status, scheduled) = match ArticleStatus::of(&article) {
+        ArticleStatus::Published => (Status::Published, None),
+        ArticleStatus::Draft => (Status::Draft, None),
+        ArticleStatus::Unlisted => (Status::Unlisted, None),
+        ArticleStatus::Scheduled(scheduled) => (Status::Scheduled, Some(scheduled)),
+    };
+
+    let kind = match ArticleKind::of(&article) {
+        ArticleKind::Post => Kind::Post,
+        ArticleKind::Page => Kind::Page,
+    };
+
+    Article {
+        title: Some(article.title),
+        url: article.url,
+        markdown: Some(article.markdown),
+        content: Some(article.content),
+        excerpt: Some(article.excerpt),
+        created: Some(article.created),
+        updated: article.updated,
+        status: Some(status.into()),
+        tags: Some(Tags { tags }),
+        scheduled,
+        kind: Some(kind.into()),
+    }
+}
+
+fn revision_to_proto(revision: RevisionModel) -> Revision {
+    Revision {
+        id: revision.revision_id,
+        title: revision.title,
+        markdown: Some(revision.markdown),
+        excerpt: revision.excerpt,
+        created: revision.created,
+    }
+}
@@ -1,59 +1,55 @@
 use std::{
-    collections::HashMap,
     net::{IpAddr, SocketAddr},
-    sync::Mutex,
+    sync::Arc,
     task::{Context, Poll},
 };
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use axum::{
-    body::Body,
-    extract::{ConnectInfo, Request},
-    http::{HeaderValue, StatusCode},
-    response::Response,
+    body::{to_bytes, Body},
+    extract::{ConnectInfo, FromRequest, Request},
+    http::{header, HeaderMap, Method, StatusCode},
+    response::{IntoResponse, Response},
+    Form,
 };
 use base64::{engine::general_purpose::STANDARD, Engine as _};
 use futures_util::future::BoxFuture;
-use log::info;
-use once_cell::sync::Lazy;
+use log::{info, warn};
+use serde::Deserialize;
 use tower::{Layer, Service};
 
-use crate::util::*;
+use entity::user::Model as User;
 
-const MAX_RETRY: u64 = 5;
-const COOLDOWN: i64 = 60 * 60 * 12; // 12 hours
+use crate::{
+    article_manager::Audience, password::constant_time_eq, session_manager::SESSION_LIFETIME,
+    site::Site, two_factor, user_manager::can_edit, util::*,
+};
 
-static BLACKLIST: Lazy<Mutex<HashMap<IpAddr, BlacklistStatus>>> =
-    Lazy::new(|| Mutex::new(HashMap::new()));
+pub const SESSION_COOKIE: &str = "session";
+const X_FORWARDED_FOR: &str = "X-Forwarded-For";
+const SEC_FETCH_SITE: &str = "Sec-Fetch-Site";
+// Sent by the editor with every request that changes something
+pub const CSRF_HEADER: &str = "X-CSRF-Token";
 
-enum BlacklistStatus {
-    Retry(u64),
-    Bannded(i64),
-}
+// Plain HTML forms cannot set headers and send the token as a field instead
+const MAX_FORM_SIZE: usize = 64 * 1024;
 
+// Lets requests through with a valid session cookie, or with HTTP Basic credentials when
+// `basic_auth` is on. Everyone else is sent to the login form.
 #[derive(Clone)]
 pub struct Auth {
-    auth_encoded: String,
+    site: Arc<Site>,
 }
 
 #[derive(Clone)]
 pub struct AuthMiddleware<S> {
     inner: S,
-    auth_encoded: String,
-}
-
-impl Default for BlacklistStatus {
-    fn default() -> Self {
-        Self::Retry(1)
-    }
+    site: Arc<Site>,
 }
 
 impl Auth {
-    pub fn new(username: &str, password: &str) -> Self {
-        let auth_decoded = format!("{}:{}", username, password);
-        let auth_encoded = STANDARD.encode(auth_decoded.as_bytes());
-
-        Self { auth_encoded }
+    pub fn new(site: Arc<Site>) -> Self {
+        Self { site }
     }
 }
 
@@ -61,134 +57,360 @@ impl<S> Layer<S> for Auth {
     type Service = AuthMiddleware<S>;
 
     fn layer(&self, inner: S) -> Self::Service {
-        AuthMiddleware::new(inner, &self.auth_encoded)
+        AuthMiddleware {
+            inner,
+            site: self.site.clone(),
+        }
     }
 }
 
-impl<S> AuthMiddleware<S> {
-    fn new(inner: S, auth_encoded: &str) -> Self {
-        Self {
-            inner,
-            auth_encoded: auth_encoded.to_owned(),
-        }
+impl<S> Service<Request> for AuthMiddleware<S>
+where
+    S: Service<Request, Response = Response> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
     }
 
-    fn auth(&self, header: &HeaderValue, ip: &IpAddr) -> bool {
-        self.auth_impl(header, ip).unwrap_or_else(|e| {
-            info!("Error in validating auth request: {e}, raw header: {header:?}");
-            false
+    fn call(&mut self, request: Request) -> Self::Future {
+        let site = self.site.clone();
+
+        // The service that was polled ready is the one to call, a fresh clone takes its place
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+
+        Box::pin(async move {
+            match authenticate(&site, request).await {
+                Ok(Ok(request)) => inner.call(request).await,
+                Ok(Err(response)) => Ok(response),
+                Err(e) => {
+                    warn!("Failed to authenticate request: {e}");
+                    Ok(StatusCode::INTERNAL_SERVER_ERROR.into_response())
+                }
+            }
         })
     }
+}
 
-    fn auth_impl(&self, header: &HeaderValue, ip: &IpAddr) -> Result<bool> {
-        const METHOD: &'static str = "Basic ";
-        let header = header.to_str()?;
+// Either the request to pass on, with the user and the session if there is one in its
+// extensions, or the response to turn it away with
+async fn authenticate(site: &Site, request: Request) -> Result<Result<Request, Response>> {
+    // Already let in with an API token by `TokenAuth`
+    if request.extensions().get::<User>().is_some() {
+        return Ok(Ok(request));
+    }
 
-        if !header.starts_with(METHOD) {
-            // An invalid request, no risk
-            return Ok(false);
-        }
+    let url = request.uri().path().to_owned();
 
-        let mut lock = BLACKLIST.lock().unwrap();
+    if site.basic_auth {
+        if let Some(header) = request.headers().get(header::AUTHORIZATION) {
+            let header = header.to_str()?.to_owned();
 
-        if let Some(status) = lock.get(&ip) {
-            match status {
-                BlacklistStatus::Retry(count) => {
-                    if count >= &MAX_RETRY {
-                        return Ok(false);
-                    }
-                }
-                BlacklistStatus::Bannded(prev_timestamp) => {
-                    if *prev_timestamp + COOLDOWN < timestamp() {
-                        lock.remove(&ip);
-                    } else {
-                        return Ok(false);
-                    }
-                }
+            let conn: &ConnectInfo<SocketAddr> = request
+                .extensions()
+                .get()
+                .ok_or(anyhow!("no connection info"))?;
+            let ip = client_ip(site, conn.0.ip(), request.headers());
+
+            if let Some(until) = site.ban_manager.banned_until(&ip).await? {
+                info!("Rejected Basic credentials from banned {ip}");
+                return Ok(Err(too_many_attempts(until)));
             }
-        }
 
-        drop(lock);
-
-        let encoded = &header[METHOD.len()..];
-
-        if encoded != self.auth_encoded {
-            let mut lock = BLACKLIST.lock().unwrap();
-
-            let new_state = match lock.get(&ip) {
-                Some(old_state) => match old_state {
-                    BlacklistStatus::Retry(count) => {
-                        if *count != MAX_RETRY - 1 {
-                            BlacklistStatus::Retry(count + 1)
-                        } else {
-                            info!("Blacklisting {ip} for {MAX_RETRY} failed auth attempt");
-                            BlacklistStatus::Bannded(timestamp())
-                        }
-                    }
-                    BlacklistStatus::Bannded(_) => BlacklistStatus::Bannded(timestamp()),
-                },
-                None => BlacklistStatus::default(),
+            let user = match check_basic(site, &header, ip).await? {
+                Some(user) => user,
+                None => return Ok(Err(unauthorized(site, &request))),
             };
 
-            lock.insert(*ip, new_state);
+            // Browsers send cached Basic credentials along with forms from other sites, and
+            // there is no session to hold a CSRF token. Those use the login form instead.
+            if !is_safe(request.method()) && from_browser(request.headers()) {
+                info!(
+                    "Rejected Basic credentials of {} for {url} sent by a browser",
+                    user.username
+                );
+                return Ok(Err(StatusCode::FORBIDDEN.into_response()));
+            }
 
-            return Ok(false);
+            let mut request = request;
+            request.extensions_mut().insert(user);
+            return Ok(Ok(request));
         }
+    }
 
-        let mut lock = BLACKLIST.lock().unwrap();
-        lock.remove(&ip);
+    let cookie = match get_cookie(request.headers(), SESSION_COOKIE) {
+        Some(cookie) => cookie,
+        None => return Ok(Err(unauthorized(site, &request))),
+    };
+
+    let session = match site.session_manager.get(cookie).await? {
+        Some(session) => session,
+        None => return Ok(Err(unauthorized(site, &request))),
+    };
+
+    // Gone if the user was deleted or renamed since
+    let user = match site
+        .user_manager
+        .get_user_by_name(&session.username)
+        .await?
+    {
+        Some(user) => user,
+        None => return Ok(Err(unauthorized(site, &request))),
+    };
+
+    // The cookie comes along with requests from other sites too, the token does not
+    let mut request = match is_safe(request.method()) {
+        true => request,
+        false => {
+            let (request, token) = csrf_token_of(request).await?;
+
+            if !constant_time_eq(token.as_bytes(), session.csrf_token.as_bytes()) {
+                info!("Rejected request to {url} without a valid CSRF token");
+                return Ok(Err(StatusCode::FORBIDDEN.into_response()));
+            }
+
+            request
+        }
+    };
 
-        Ok(true)
+    request.extensions_mut().insert(session);
+    request.extensions_mut().insert(user);
+
+    Ok(Ok(request))
+}
+
+fn is_safe(method: &Method) -> bool {
+    matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS)
+}
+
+// Scripts send none of this, browsers send the headers with every request and the content
+// types are the ones a form on another site can post without asking
+fn from_browser(headers: &HeaderMap) -> bool {
+    const FORM_TYPES: &[&str] = &[
+        "application/x-www-form-urlencoded",
+        "multipart/form-data",
+        "text/plain",
+    ];
+
+    if headers.contains_key(header::ORIGIN) || headers.contains_key(SEC_FETCH_SITE) {
+        return true;
     }
+
+    headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|content_type| content_type.to_str().ok())
+        .is_some_and(|content_type| {
+            let content_type = content_type.trim_start().to_ascii_lowercase();
+            FORM_TYPES.iter().any(|form| content_type.starts_with(form))
+        })
 }
 
-impl<S> Service<Request> for AuthMiddleware<S>
-where
-    S: Service<Request, Response = Response> + Send + 'static,
-    S::Future: Send + 'static,
-{
-    type Response = S::Response;
-    type Error = S::Error;
-    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+// From the header, or from the `csrf_token` field of a form, in which case the body is read
+// and put back for the handler
+async fn csrf_token_of(request: Request) -> Result<(Request, String)> {
+    #[derive(Deserialize)]
+    struct CsrfForm {
+        csrf_token: Option<String>,
+    }
 
-    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
-        self.inner.poll_ready(cx)
+    if let Some(token) = request.headers().get(CSRF_HEADER) {
+        let token = token.to_str().unwrap_or_default().to_owned();
+        return Ok((request, token));
     }
 
-    fn call(&mut self, request: Request) -> Self::Future {
-        let url = request.uri().path();
+    let is_form = request
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|content_type| content_type.to_str().ok())
+        .is_some_and(|content_type| content_type.starts_with("application/x-www-form-urlencoded"));
 
-        let auth_header = match request.headers().get("Authorization") {
-            Some(ah) => ah,
-            None => {
-                let resp = unauthorized(url);
-                return Box::pin(async move { Ok(resp) });
-            }
-        };
+    if !is_form {
+        return Ok((request, String::new()));
+    }
+
+    let (parts, body) = request.into_parts();
+    let body = to_bytes(body, MAX_FORM_SIZE).await?;
 
-        let conn: &ConnectInfo<SocketAddr> = request.extensions().get().unwrap();
-        let ip = &conn.0.ip();
+    let form = Request::from_parts(parts.clone(), Body::from(body.clone()));
+    let token = match Form::<CsrfForm>::from_request(form, &()).await {
+        Ok(Form(form)) => form.csrf_token.unwrap_or_default(),
+        Err(_) => String::new(),
+    };
 
-        if !self.auth(auth_header, ip) {
-            let resp = unauthorized(url);
-            return Box::pin(async move { Ok(resp) });
+    Ok((Request::from_parts(parts, Body::from(body)), token))
+}
+
+async fn check_basic(site: &Site, header: &str, ip: IpAddr) -> Result<Option<User>> {
+    const METHOD: &str = "Basic ";
+
+    let encoded = match header.strip_prefix(METHOD) {
+        Some(encoded) => encoded,
+        // An invalid request, no risk
+        None => return Ok(None),
+    };
+
+    let decoded = STANDARD
+        .decode(encoded)
+        .ok()
+        .and_then(|decoded| String::from_utf8(decoded).ok());
+
+    let (username, password) = match decoded.as_deref().and_then(|d| d.split_once(':')) {
+        Some((username, password)) => (username.to_owned(), password.to_owned()),
+        None => {
+            info!("Malformed Basic credentials from {ip}");
+            return Ok(None);
         }
+    };
 
-        let future = self.inner.call(request);
+    let user = match site.user_manager.authenticate(&username, &password).await? {
+        Some(user) => user,
+        None => {
+            site.ban_manager.record_failure(&ip).await?;
+            return Ok(None);
+        }
+    };
 
-        Box::pin(async move {
-            let response: Response = future.await?;
-            Ok(response)
-        })
+    site.ban_manager.record_success(&ip).await?;
+
+    // There is no way to send a code along, so the password alone must not be enough
+    if two_factor::is_enabled(&user) {
+        info!(
+            "Refused Basic credentials of {username}, who has two-factor authentication on, use an API token instead"
+        );
+        return Ok(None);
     }
+
+    Ok(Some(user))
 }
 
-fn unauthorized(url: &str) -> Response<Body> {
-    info!("Unauthorized request to {url}");
+// The address the request came from. Behind one of our reverse proxies that is the last one
+// in X-Forwarded-For the proxies did not add themselves, anything before it could be made up.
+pub fn client_ip(site: &Site, peer: IpAddr, headers: &HeaderMap) -> IpAddr {
+    let peer = peer.to_canonical();
+    let trusted = site.get_trusted_proxies();
+
+    if !trusted.contains(&peer) {
+        return peer;
+    }
+
+    let forwarded: Vec<_> = headers
+        .get_all(X_FORWARDED_FOR)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|ip| ip.trim())
+        .collect();
+
+    let mut client = peer;
+
+    for ip in forwarded.into_iter().rev() {
+        client = match ip.parse::<IpAddr>() {
+            Ok(ip) => ip.to_canonical(),
+            Err(_) => {
+                warn!("Ignored malformed X-Forwarded-For entry {ip}");
+                return client;
+            }
+        };
+
+        if !trusted.contains(&client) {
+            break;
+        }
+    }
+
+    client
+}
+
+// For banned addresses, with a hint when to come back
+pub fn too_many_attempts(until: i64) -> Response<Body> {
+    let retry_after = (until - timestamp()).max(0);
 
     Response::builder()
-        .status(StatusCode::UNAUTHORIZED)
-        .header("WWW-Authenticate", "Basic")
+        .status(StatusCode::TOO_MANY_REQUESTS)
+        .header(header::RETRY_AFTER, retry_after)
         .body(Body::empty())
         .unwrap()
 }
+
+// The response to turn the user away with, if the article is someone else's they may not touch.
+// Articles that do not exist are left to the handler.
+pub async fn authorize_article(site: &Site, user: &User, url: &str) -> Option<Response> {
+    let article = match site
+        .article_manager
+        .get_article(url, Audience::Editor)
+        .await
+    {
+        Ok(article) => article,
+        Err(e) => {
+            warn!("Failed to look up article {url}: {e}");
+            return Some(StatusCode::INTERNAL_SERVER_ERROR.into_response());
+        }
+    };
+
+    match article {
+        Some(article) if !can_edit(user, &article) => {
+            info!("{} is not allowed to edit article {url}", user.username);
+            Some(StatusCode::FORBIDDEN.into_response())
+        }
+        _ => None,
+    }
+}
+
+pub fn get_cookie<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .filter_map(|pair| pair.trim().split_once('='))
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| value)
+}
+
+// An empty value with no lifetime clears the cookie
+pub fn session_cookie(site: &Site, value: &str) -> String {
+    let max_age = match value.is_empty() {
+        true => 0,
+        false => SESSION_LIFETIME,
+    };
+
+    // Browsers drop Secure cookies set over plain HTTP, so only when the site is served over TLS
+    let secure = match site.get_base_url().starts_with("https://") {
+        true => "; Secure",
+        false => "",
+    };
+
+    format!("{SESSION_COOKIE}={value}; Path=/; Max-Age={max_age}; HttpOnly; SameSite=Lax{secure}")
+}
+
+fn unauthorized(site: &Site, request: &Request) -> Response<Body> {
+    let url = request.uri().path();
+    info!("Unauthorized request to {url}");
+
+    // People opening the editor get the login form, scripts and the API get a status
+    if request.method() == Method::GET && !url.starts_with("/api/") {
+        let next = request
+            .uri()
+            .path_and_query()
+            .map(|pq| pq.as_str())
+            .unwrap_or(url);
+
+        return Response::builder()
+            .status(StatusCode::SEE_OTHER)
+            .header(header::LOCATION, format!("/login?next={}", urlencode(next)))
+            .body(Body::empty())
+            .unwrap();
+    }
+
+    let mut response = Response::builder().status(StatusCode::UNAUTHORIZED);
+
+    if site.basic_auth {
+        response = response.header(header::WWW_AUTHENTICATE, "Basic");
+    }
+
+    response.body(Body::empty()).unwrap()
+}
@@ -0,0 +1,113 @@
+use std::{sync::Arc, time::Duration};
+
+use anyhow::Result;
+use axum::{
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    Extension, Form,
+};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use tokio::time;
+
+use entity::{session::Model as Session, user::Model as User};
+
+use crate::{site::Site, user_manager::Role, util::*};
+
+const PURGE_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+#[derive(Deserialize)]
+pub struct LiftForm {
+    ip: String,
+}
+
+// Runs for as long as the server does, so the table only holds what still matters
+pub async fn purge_bans(site: Arc<Site>) {
+    let mut interval = time::interval(PURGE_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        if let Err(e) = site.ban_manager.purge_expired().await {
+            warn!("Failed to purge expired bans: {e}");
+        }
+    }
+}
+
+pub async fn bans_handler(
+    Extension(site): Extension<Arc<Site>>,
+    Extension(user): Extension<User>,
+    session: Option<Extension<Session>>,
+) -> impl IntoResponse {
+    if Role::of(&user) != Role::Admin {
+        return StatusCode::FORBIDDEN.into_response();
+    }
+
+    render(&site, StatusCode::OK, session.as_ref().map(|s| &s.0)).await
+}
+
+pub async fn ban_lift_handler(
+    Extension(site): Extension<Arc<Site>>,
+    Extension(user): Extension<User>,
+    Form(form): Form<LiftForm>,
+) -> impl IntoResponse {
+    if Role::of(&user) != Role::Admin {
+        return StatusCode::FORBIDDEN.into_response();
+    }
+
+    match site.ban_manager.lift_ban(&form.ip).await {
+        Ok(true) => (StatusCode::SEE_OTHER, [(header::LOCATION, "/editor/bans")]).into_response(),
+        Ok(false) => StatusCode::NOT_FOUND.into_response(),
+        Err(e) => {
+            warn!("Failed to lift ban on {}: {e}", form.ip);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+async fn render(site: &Site, status: StatusCode, session: Option<&Session>) -> Response {
+    let text = match bans_impl(site, session).await {
+        Ok(text) => text,
+        Err(e) => {
+            warn!("Failed to handle request for /editor/bans: {e}");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    let headers = [(header::CONTENT_TYPE, "text/html")];
+
+    (status, headers, text).into_response()
+}
+
+async fn bans_impl(site: &Site, session: Option<&Session>) -> Result<String> {
+    #[derive(Serialize)]
+    struct BanItem {
+        ip: String,
+        failures: i32,
+        last_failure: String,
+        banned_until: String,
+    }
+
+    let bans: Vec<_> = site
+        .ban_manager
+        .get_bans()
+        .await?
+        .into_iter()
+        .map(|ban| BanItem {
+            ip: ban.ip,
+            failures: ban.failures,
+            last_failure: site.format_time(ban.last_failure),
+            banned_until: site.format_time(ban.banned_until.unwrap_or_default()),
+        })
+        .collect();
+
+    let mut context = site.base_context();
+    context.insert("title", "Bans"); // TODO: i18n
+    context.insert("csrf_token", &session.map(|s| &s.csrf_token));
+    context.insert("bans", &bans);
+
+    let rendered = site.render("bans.html", &context)?;
+    let minified = minify_html(rendered)?;
+
+    Ok(minified)
+}
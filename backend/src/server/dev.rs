@@ -0,0 +1,75 @@
+use std::{convert::Infallible, sync::Arc, time::Duration};
+
+use axum::{
+    http::StatusCode,
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse,
+    },
+    Extension,
+};
+use futures::stream;
+use log::{info, warn};
+use tokio::{sync::broadcast::error::RecvError, time};
+
+use crate::{site::Site, theme::snapshot};
+
+// Often enough to feel instant after saving a file, rare enough to not matter
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+// Polls the template and asset directories and reloads everything when a file was added,
+// removed or changed
+pub async fn watch_theme(site: Arc<Site>) {
+    let paths = site.get_theme_paths();
+    info!("Watching {paths:?} for changes");
+
+    let mut last = snapshot(&paths).ok();
+    let mut interval = time::interval(POLL_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        let current = match snapshot(&paths) {
+            Ok(current) => Some(current),
+            // A file can disappear between listing and reading its metadata
+            Err(e) => {
+                warn!("Failed to scan theme directories: {e}");
+                continue;
+            }
+        };
+
+        if current == last {
+            continue;
+        }
+
+        last = current;
+
+        match site.reload_theme() {
+            Ok(()) => info!("Reloaded templates and assets"),
+            Err(e) => warn!("Failed to reload templates and assets: {e:#}"),
+        }
+    }
+}
+
+// Server-sent events telling open pages to reload, see the end of base.html
+pub async fn reload_handler(Extension(site): Extension<Arc<Site>>) -> impl IntoResponse {
+    let receiver = match site.subscribe_reload() {
+        Some(receiver) => receiver,
+        None => return StatusCode::NOT_FOUND.into_response(),
+    };
+
+    let events = stream::unfold(receiver, |mut receiver| async move {
+        match receiver.recv().await {
+            // Missing a few reloads still means there was one
+            Ok(()) | Err(RecvError::Lagged(_)) => {
+                let event = Event::default().event("reload").data("");
+                Some((Ok::<_, Infallible>(event), receiver))
+            }
+            Err(RecvError::Closed) => None,
+        }
+    });
+
+    Sse::new(events)
+        .keep_alive(KeepAlive::default())
+        .into_response()
+}
@@ -2,17 +2,47 @@ use std::sync::Arc;
 
 use anyhow::Result;
 use axum::{
+    extract::Query,
     http::{header, StatusCode},
     response::IntoResponse,
     Extension,
 };
 use log::warn;
+use serde::{Deserialize, Serialize};
 
-use crate::{site::Site, util::*};
+use entity::{session::Model as Session, user::Model as User};
 
-pub async fn editor_handler(Extension(site): Extension<Arc<Site>>) -> impl IntoResponse {
-    let text = match editor_impl(site).await {
-        Ok(text) => text,
+use crate::{
+    article_manager::{ArticleKind, ArticleStatus, Audience},
+    server::auth::authorize_article,
+    site::Site,
+    user_manager::Role,
+    util::*,
+};
+
+#[derive(Deserialize)]
+pub struct EditorQuery {
+    url: Option<String>,
+}
+
+pub async fn editor_handler(
+    Extension(site): Extension<Arc<Site>>,
+    Extension(user): Extension<User>,
+    session: Option<Extension<Session>>,
+    Query(query): Query<EditorQuery>,
+) -> impl IntoResponse {
+    if let Some(url) = &query.url {
+        if let Some(response) = authorize_article(&site, &user, url).await {
+            return response;
+        }
+    }
+
+    // Basic auth has no session, and only scripts get to change anything with it
+    let csrf_token = session.map(|Extension(session)| session.csrf_token);
+
+    let text = match editor_impl(site, &user, query.url.as_deref(), csrf_token.as_deref()).await {
+        Ok(Some(text)) => text,
+        Ok(None) => return StatusCode::NOT_FOUND.into_response(),
         Err(e) => {
             warn!("Failed to handle request for /editor: {e}");
             return StatusCode::INTERNAL_SERVER_ERROR.into_response();
@@ -24,12 +54,75 @@ pub async fn editor_handler(Extension(site): Extension<Arc<Site>>) -> impl IntoR
     (StatusCode::OK, headers, text).into_response()
 }
 
-async fn editor_impl(site: Arc<Site>) -> Result<String> {
+async fn editor_impl(
+    site: Arc<Site>,
+    user: &User,
+    url: Option<&str>,
+    csrf_token: Option<&str>,
+) -> Result<Option<String>> {
+    #[derive(Serialize)]
+    struct Article {
+        title: String,
+        url: String,
+        excerpt: String,
+        markdown: String,
+        tags: String,
+        status: &'static str,
+        unlisted: bool,
+        scheduled: Option<i64>,
+        page: bool,
+    }
+
     let mut context = site.base_context();
     context.insert("title", "Editor"); // TODO: i18n
+    context.insert("csrf_token", &csrf_token);
+    context.insert("role", &Role::of(user));
+
+    // Editing an existing article, otherwise we are writing a new one
+    if let Some(url) = url {
+        let article = match site
+            .article_manager
+            .get_article(url, Audience::Editor)
+            .await?
+        {
+            Some(article) => article,
+            None => return Ok(None),
+        };
+
+        let tags = site
+            .article_manager
+            .get_article_tags(article.article_id)
+            .await?
+            .join(", ");
+
+        let unlisted = ArticleStatus::of(&article) == ArticleStatus::Unlisted;
+        let page = ArticleKind::of(&article) == ArticleKind::Page;
+
+        // TODO: i18n
+        let (status, scheduled) = match ArticleStatus::of(&article) {
+            ArticleStatus::Published => ("Published", None),
+            ArticleStatus::Draft => ("Draft", None),
+            ArticleStatus::Unlisted => ("Unlisted", None),
+            ArticleStatus::Scheduled(scheduled) => ("Scheduled", Some(scheduled)),
+        };
+
+        let article = Article {
+            title: article.title,
+            url: article.url,
+            excerpt: article.excerpt,
+            markdown: article.markdown,
+            tags,
+            status,
+            unlisted,
+            scheduled,
+            page,
+        };
+
+        context.insert("article", &article);
+    }
 
     let rendered = site.render("editor.html", &context)?;
     let minified = minify_html(rendered)?;
 
-    Ok(minified)
+    Ok(Some(minified))
 }
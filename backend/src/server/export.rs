@@ -0,0 +1,383 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use anyhow::{anyhow, Result};
+use log::{info, warn};
+use tokio::fs;
+
+use crate::{
+    article_manager::Audience,
+    server::{
+        archive::archive_impl,
+        article::article_impl,
+        feed::{feed_impl, Format},
+        index::{index_impl, ARTICLES_PER_PAGE},
+        page::page_impl,
+        sitemap::sitemap_impl,
+        tag::{tag_impl, tags_impl},
+    },
+    site::Site,
+    util::*,
+};
+
+// Routes that are files rather than directories holding an index.html
+const FILE_ROUTES: &[&str] = &["atom.xml", "feed.xml", "robots.txt", "sitemap.xml"];
+
+struct Export {
+    output: PathBuf,
+    files: usize,
+}
+
+// Renders every public route into `output` as static files. Links are made relative, so the
+// result works from any static host or straight from disk. Search needs the backend and is
+// left out.
+pub async fn export_site(mut site: Site, output: &str) -> Result<()> {
+    site.static_export = true;
+    let site = Arc::new(site);
+    let mut export = Export {
+        output: PathBuf::from(output),
+        files: 0,
+    };
+
+    let metadata = site
+        .article_manager
+        .get_all_article_metadatas(Audience::Public)
+        .await?;
+
+    export
+        .write_html("/", &index_impl(site.clone(), 1).await?)
+        .await?;
+
+    // Page 1 is linked to from page 2
+    for page in 1..=metadata.len().div_ceil(ARTICLES_PER_PAGE) {
+        let html = index_impl(site.clone(), page).await?;
+        export.write_html(&format!("/page/{page}"), &html).await?;
+    }
+
+    export
+        .write_html("/archive", &archive_impl(site.clone()).await?)
+        .await?;
+    export
+        .write_html("/tags", &tags_impl(site.clone()).await?)
+        .await?;
+
+    for tag in site.article_manager.get_tags(Audience::Public).await? {
+        let route = format!("/tag/{}", urlencode(&tag.tag));
+
+        if let Some(html) = tag_impl(site.clone(), &tag.tag, 1).await? {
+            export.write_html(&route, &html).await?;
+        }
+
+        for page in 1..=tag.count.div_ceil(ARTICLES_PER_PAGE) {
+            if let Some(html) = tag_impl(site.clone(), &tag.tag, page).await? {
+                export
+                    .write_html(&format!("{route}/page/{page}"), &html)
+                    .await?;
+            }
+        }
+    }
+
+    for article in metadata {
+        if let Some(html) = article_impl(site.clone(), &article.url, Audience::Public).await? {
+            let route = format!("/article/{}", urlencode(&article.url));
+            export.write_html(&route, &html).await?;
+        }
+    }
+
+    let pages = site
+        .article_manager
+        .get_page_metadatas(Audience::Public)
+        .await?;
+
+    for page in pages {
+        if let Some(html) = page_impl(site.clone(), &page.url).await? {
+            export
+                .write_html(&format!("/{}", urlencode(&page.url)), &html)
+                .await?;
+        }
+    }
+
+    // Feeds and the sitemap already use absolute links built from base_url
+    let rss = feed_impl(site.clone(), Format::Rss).await?;
+    export.write("/feed.xml", rss.as_bytes()).await?;
+
+    let atom = feed_impl(site.clone(), Format::Atom).await?;
+    export.write("/atom.xml", atom.as_bytes()).await?;
+
+    let sitemap = sitemap_impl(site.clone()).await?;
+    export.write("/sitemap.xml", sitemap.as_bytes()).await?;
+
+    export
+        .write("/robots.txt", site.get_robots_txt().as_bytes())
+        .await?;
+
+    for path in site.get_asset_paths() {
+        let route = format!("/asset/{path}");
+
+        let (data, mime_type) = match site.get_asset(&path) {
+            Some(asset) => asset,
+            None => continue,
+        };
+
+        match mime_type {
+            "text/css" => {
+                let css = std::str::from_utf8(&data)?;
+                export.write_css(&route, css).await?;
+            }
+            _ => export.write(&route, &data).await?,
+        }
+    }
+
+    let media = site
+        .media_manager
+        .copy_all(&export.output.join("media"))
+        .await?;
+
+    info!(
+        "Exported {} file(s) and {media} media file(s) to {output}",
+        export.files
+    );
+
+    Ok(())
+}
+
+impl Export {
+    async fn write_html(&mut self, route: &str, html: &str) -> Result<()> {
+        let html = relativize_html(html, depth_of(route));
+        self.write(route, html.as_bytes()).await
+    }
+
+    async fn write_css(&mut self, route: &str, css: &str) -> Result<()> {
+        let prefix = "../".repeat(depth_of(route));
+
+        let css = css
+            .replace("url('/", &format!("url('{prefix}"))
+            .replace("url(\"/", &format!("url(\"{prefix}"))
+            .replace("url(/", &format!("url({prefix}"));
+
+        self.write(route, css.as_bytes()).await
+    }
+
+    async fn write(&mut self, route: &str, data: &[u8]) -> Result<()> {
+        let path = match self.path_of(route) {
+            Ok(path) => path,
+            Err(e) => {
+                // One odd article url should not stop the whole export
+                warn!("Skipped {route}: {e}");
+                return Ok(());
+            }
+        };
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+
+        fs::write(&path, data).await?;
+        self.files += 1;
+
+        Ok(())
+    }
+
+    fn path_of(&self, route: &str) -> Result<PathBuf> {
+        let mut path = self.output.clone();
+
+        for segment in file_of(route).split('/') {
+            let segment = urldecode(segment)?;
+
+            if matches!(segment.as_str(), "" | "." | "..") || segment.contains(['/', '\\']) {
+                return Err(anyhow!("unsafe path segment {segment:?}"));
+            }
+
+            path.push(Path::new(&segment));
+        }
+
+        Ok(path)
+    }
+}
+
+// Routes become `<route>/index.html`, so plain object storage needs no rewrite rules
+fn file_of(route: &str) -> String {
+    let path = route.trim_matches('/');
+
+    let is_file =
+        path.starts_with("asset/") || path.starts_with("media/") || FILE_ROUTES.contains(&path);
+
+    match (path.is_empty(), is_file) {
+        (true, _) => "index.html".to_owned(),
+        (false, true) => path.to_owned(),
+        (false, false) => format!("{path}/index.html"),
+    }
+}
+
+// Number of directories between the output root and the file of a route
+fn depth_of(route: &str) -> usize {
+    file_of(route).matches('/').count()
+}
+
+// Links to our own routes, e.g. `/article/foo`, from a file `depth` directories down
+fn relative_link(link: &str, depth: usize) -> Option<String> {
+    // Absolute, protocol relative and already relative links are left alone
+    if !link.starts_with('/') || link.starts_with("//") {
+        return None;
+    }
+
+    let (path, suffix) = link.split_at(link.find(['?', '#']).unwrap_or(link.len()));
+
+    Some(format!("{}{}{suffix}", "../".repeat(depth), file_of(path)))
+}
+
+fn relativize_value(name: &str, value: &str, depth: usize) -> String {
+    // A srcset is a list of `<url> <descriptor>`
+    if name == "srcset" {
+        let sources: Vec<_> = value
+            .split(',')
+            .map(|source| {
+                let source = source.trim();
+                let (link, descriptor) = source.split_at(source.find(' ').unwrap_or(source.len()));
+
+                match relative_link(link, depth) {
+                    Some(link) => format!("{link}{descriptor}"),
+                    None => source.to_owned(),
+                }
+            })
+            .collect();
+
+        return sources.join(", ");
+    }
+
+    relative_link(value, depth).unwrap_or_else(|| value.to_owned())
+}
+
+// Module imports like `import init from '/asset/blog.js'`, which the browser resolves against
+// the page just like a link
+fn relativize_imports(script: &str, depth: usize) -> String {
+    let mut output = String::with_capacity(script.len());
+    let mut rest = script;
+
+    while let Some(start) = rest.find("from") {
+        let (head, tail) = rest.split_at(start + "from".len());
+        output.push_str(head);
+
+        let spec = tail.trim_start();
+        let (quote, link) = match spec.chars().next() {
+            Some(quote @ ('\'' | '"')) => (quote, &spec[1..]),
+            _ => {
+                rest = tail;
+                continue;
+            }
+        };
+
+        let end = match link.find(quote) {
+            Some(end) => end,
+            None => {
+                rest = tail;
+                continue;
+            }
+        };
+
+        output.push_str(&tail[..tail.len() - spec.len()]);
+        output.push(quote);
+        match relative_link(&link[..end], depth) {
+            // `asset/blog.js` would be taken for a package name
+            Some(relative) if depth == 0 => output.push_str(&format!("./{relative}")),
+            Some(relative) => output.push_str(&relative),
+            None => output.push_str(&link[..end]),
+        }
+        rest = &link[end..];
+    }
+
+    output.push_str(rest);
+
+    output
+}
+
+// Rewrites href, src and srcset attributes, quoted or not since the HTML has been minified
+fn relativize_html(html: &str, depth: usize) -> String {
+    let bytes = html.as_bytes();
+    let mut output = String::with_capacity(html.len());
+    let mut last = 0;
+    let mut i = 0;
+
+    while i < bytes.len() {
+        // Only tags are looked at, text that happens to contain `href=` stays as it is
+        if bytes[i] != b'<' || !bytes.get(i + 1).is_some_and(u8::is_ascii_alphabetic) {
+            i += 1;
+            continue;
+        }
+
+        i += 1;
+        let tag_start = i;
+        while i < bytes.len() && bytes[i].is_ascii_alphanumeric() {
+            i += 1;
+        }
+        let tag = html[tag_start..i].to_ascii_lowercase();
+
+        while i < bytes.len() && bytes[i] != b'>' {
+            let name_start = i;
+            while i < bytes.len()
+                && !matches!(bytes[i], b'=' | b'>')
+                && !bytes[i].is_ascii_whitespace()
+            {
+                i += 1;
+            }
+            let name = html[name_start..i].to_ascii_lowercase();
+
+            match bytes.get(i) {
+                Some(b'=') => i += 1,
+                Some(b'>') | None => break,
+                Some(_) => {
+                    i += 1;
+                    continue;
+                }
+            }
+
+            let (value_start, value_end) = match bytes.get(i) {
+                Some(&quote @ (b'"' | b'\'')) => {
+                    let start = i + 1;
+                    let end = html[start..]
+                        .find(quote as char)
+                        .map_or(bytes.len(), |end| start + end);
+                    i = (end + 1).min(bytes.len());
+                    (start, end)
+                }
+                _ => {
+                    let start = i;
+                    while i < bytes.len() && bytes[i] != b'>' && !bytes[i].is_ascii_whitespace() {
+                        i += 1;
+                    }
+                    (start, i)
+                }
+            };
+
+            if matches!(name.as_str(), "href" | "src" | "srcset") {
+                output.push_str(&html[last..value_start]);
+                output.push_str(&relativize_value(
+                    &name,
+                    &html[value_start..value_end],
+                    depth,
+                ));
+                last = value_end;
+            }
+        }
+
+        // Scripts and styles may contain `<` that does not start a tag
+        if matches!(tag.as_str(), "script" | "style") {
+            let body_start = (i + 1).min(bytes.len());
+            i = html[i..]
+                .find(&format!("</{tag}"))
+                .map_or(bytes.len(), |end| i + end);
+
+            if tag == "script" && body_start < i {
+                output.push_str(&html[last..body_start]);
+                output.push_str(&relativize_imports(&html[body_start..i], depth));
+                last = i;
+            }
+        }
+    }
+
+    output.push_str(&html[last..]);
+
+    output
+}
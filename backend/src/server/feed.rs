@@ -0,0 +1,141 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use axum::{
+    http::{header, StatusCode},
+    response::IntoResponse,
+    Extension,
+};
+use chrono::{DateTime, Utc};
+use log::warn;
+use serde::Serialize;
+
+use crate::{
+    article_manager::{published_at, Audience},
+    site::{Site, Str},
+    util::*,
+};
+
+const FEED_ARTICLES: usize = 20;
+
+// Feed and entry ids have to be absolute, without a base_url they become `tag:` URIs
+const TAG_AUTHORITY: &str = "localhost";
+const TAG_DATE: &str = "2000";
+
+#[derive(Clone, Copy)]
+pub enum Format {
+    Rss,
+    Atom,
+}
+
+pub async fn rss_handler(Extension(site): Extension<Arc<Site>>) -> impl IntoResponse {
+    feed_response(site, Format::Rss).await
+}
+
+pub async fn atom_handler(Extension(site): Extension<Arc<Site>>) -> impl IntoResponse {
+    feed_response(site, Format::Atom).await
+}
+
+async fn feed_response(site: Arc<Site>, format: Format) -> impl IntoResponse {
+    let text = match feed_impl(site, format).await {
+        Ok(text) => text,
+        Err(e) => {
+            warn!("Failed to generate feed: {e}");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    let mime_type = match format {
+        Format::Rss => "application/rss+xml",
+        Format::Atom => "application/atom+xml",
+    };
+
+    let headers = [(header::CONTENT_TYPE, mime_type)];
+
+    (StatusCode::OK, headers, text).into_response()
+}
+
+pub async fn feed_impl(site: Arc<Site>, format: Format) -> Result<String> {
+    #[derive(Serialize)]
+    struct Article {
+        title: String,
+        link: String,
+        id: String,
+        author: Str,
+        excerpt: String,
+        content: String,
+        published: String,
+        updated: String,
+    }
+
+    let base_url = site.get_base_url();
+    let permalink = !base_url.is_empty();
+
+    let latest_articles = site
+        .article_manager
+        .get_latest_articles(Audience::Public, FEED_ARTICLES)
+        .await?;
+
+    let mut articles = vec![];
+    let mut latest = None;
+
+    for article in latest_articles {
+        let author = site.get_article_author(&article).await?;
+
+        let published = published_at(&article);
+        let updated = article.updated.unwrap_or(published).max(published);
+        latest = latest.max(Some(updated));
+
+        let link = format!("{base_url}/article/{}", urlencode(&article.url));
+
+        articles.push(Article {
+            title: article.title,
+            id: feed_id(permalink, &link),
+            link,
+            author,
+            excerpt: article.excerpt,
+            content: article.content,
+            published: format_feed_time(published, format),
+            updated: format_feed_time(updated, format),
+        });
+    }
+
+    // Atom requires a feed level timestamp even when there are no entries
+    let updated = format_feed_time(latest.unwrap_or_else(timestamp), format);
+
+    let mut context = site.base_context();
+    context.insert("base_url", base_url);
+    context.insert("permalink", &permalink);
+    context.insert("feed_id", &feed_id(permalink, &format!("{base_url}/")));
+    context.insert("author", &site.get_author());
+    context.insert("updated", &updated);
+    context.insert("articles", &articles);
+
+    let template = match format {
+        Format::Rss => "rss.xml",
+        Format::Atom => "atom.xml",
+    };
+
+    let rendered = site.render(template, &context)?;
+
+    Ok(rendered)
+}
+
+fn feed_id(permalink: bool, link: &str) -> String {
+    match permalink {
+        true => link.to_owned(),
+        false => format!("tag:{TAG_AUTHORITY},{TAG_DATE}:{link}"),
+    }
+}
+
+fn format_feed_time(timestamp: i64, format: Format) -> String {
+    let dt = DateTime::<Utc>::from_timestamp(timestamp, 0).unwrap_or_else(|| {
+        warn!("Invalid timestamp {timestamp}");
+        DateTime::<Utc>::UNIX_EPOCH
+    });
+
+    match format {
+        Format::Rss => dt.to_rfc2822(),
+        Format::Atom => dt.to_rfc3339(),
+    }
+}
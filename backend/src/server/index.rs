@@ -8,10 +8,15 @@ use axum::{
     Extension,
 };
 use log::warn;
+use tera::Context;
 
-use crate::{site::Site, util::*};
+use crate::{
+    article_manager::{ArticleMetadata, Audience},
+    site::Site,
+    util::*,
+};
 
-const ARTICLES_PER_PAGE: usize = 10;
+pub const ARTICLES_PER_PAGE: usize = 10;
 
 pub async fn index_handler(Extension(site): Extension<Arc<Site>>) -> impl IntoResponse {
     let text = match index_impl(site, 1).await {
@@ -45,16 +50,30 @@ pub async fn index_page_handler(
     (StatusCode::OK, headers, text).into_response()
 }
 
-async fn index_impl(site: Arc<Site>, page: usize) -> Result<String> {
+pub async fn index_impl(site: Arc<Site>, page: usize) -> Result<String> {
     // TODO: could be optimized
     let metadata: Vec<_> = site
         .article_manager
-        .get_all_article_metadatas()
+        .get_all_article_metadatas(Audience::Public)
         .await?
         .into_iter()
         .collect();
 
-    let page = page - 1; // 1-indexed to 0-indexed
+    let mut context = site.base_context();
+    context.insert("title", "Home"); // TODO: i18n
+
+    render_article_list(&site, context, metadata, page, "/page")
+}
+
+// Shared by every paginated article listing, `page_prefix` is where the page number is appended
+pub fn render_article_list(
+    site: &Site,
+    mut context: Context,
+    metadata: Vec<ArticleMetadata>,
+    page: usize,
+    page_prefix: &str,
+) -> Result<String> {
+    let page = page.max(1) - 1; // 1-indexed to 0-indexed
 
     let has_next = metadata.len() > (page + 1) * ARTICLES_PER_PAGE;
     let has_prev = page > 0;
@@ -65,9 +84,8 @@ async fn index_impl(site: Arc<Site>, page: usize) -> Result<String> {
         .take(ARTICLES_PER_PAGE)
         .collect();
 
-    let mut context = site.base_context();
     context.insert("articles", &metadata);
-    context.insert("title", "Home"); // TODO: i18n
+    context.insert("page_prefix", page_prefix);
 
     if has_next {
         // 0-indexed to 1-indexed
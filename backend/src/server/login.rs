@@ -0,0 +1,234 @@
+use std::{net::SocketAddr, sync::Arc};
+
+use anyhow::Result;
+use axum::{
+    extract::{ConnectInfo, Query},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Extension, Form,
+};
+use log::{info, warn};
+use serde::Deserialize;
+
+use crate::{
+    password::constant_time_eq,
+    server::auth::{client_ip, get_cookie, session_cookie, SESSION_COOKIE},
+    site::Site,
+    two_factor,
+    util::*,
+};
+
+#[derive(Deserialize)]
+pub struct LoginQuery {
+    next: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct LoginForm {
+    username: String,
+    password: String,
+    // Only needed for accounts with two-factor authentication on
+    code: Option<String>,
+    next: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct LogoutForm {
+    csrf_token: String,
+}
+
+pub async fn login_page_handler(
+    Extension(site): Extension<Arc<Site>>,
+    Query(query): Query<LoginQuery>,
+) -> impl IntoResponse {
+    login_response(&site, StatusCode::OK, query.next.as_deref(), None).await
+}
+
+pub async fn login_handler(
+    Extension(site): Extension<Arc<Site>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Form(form): Form<LoginForm>,
+) -> impl IntoResponse {
+    let ip = client_ip(&site, addr.ip(), &headers);
+    let next = form.next.as_deref();
+
+    match site.ban_manager.banned_until(&ip).await {
+        Ok(Some(until)) => return banned_response(&site, next, until).await,
+        Ok(None) => {}
+        Err(e) => {
+            warn!("Failed to look up ban on {ip}: {e}");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    }
+
+    let user = match site
+        .user_manager
+        .authenticate(&form.username, &form.password)
+        .await
+    {
+        Ok(user) => user,
+        Err(e) => {
+            warn!("Failed to check credentials: {e}");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    // The code is only looked at with the right password, so guessing it means knowing that
+    let code = form.code.as_deref().unwrap_or_default();
+    let user = match user {
+        Some(user) if two_factor::is_enabled(&user) => {
+            match two_factor::verify(&site.user_manager, &user, code, timestamp()).await {
+                Ok(true) => Some(user),
+                Ok(false) => None,
+                Err(e) => {
+                    warn!("Failed to verify two-factor code: {e}");
+                    return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+                }
+            }
+        }
+        user => user,
+    };
+
+    let user = match user {
+        Some(user) => user,
+        None => {
+            info!("Failed login from {ip}");
+
+            // This one might have been the last straw
+            let banned = match site.ban_manager.record_failure(&ip).await {
+                Ok(()) => site.ban_manager.banned_until(&ip).await,
+                Err(e) => Err(e),
+            };
+
+            // TODO: i18n
+            let error = "Wrong username, password or code";
+            return match banned {
+                Ok(Some(until)) => banned_response(&site, next, until).await,
+                Ok(None) => {
+                    login_response(&site, StatusCode::UNAUTHORIZED, next, Some(error)).await
+                }
+                Err(e) => {
+                    warn!("Failed to record failed login from {ip}: {e}");
+                    StatusCode::INTERNAL_SERVER_ERROR.into_response()
+                }
+            };
+        }
+    };
+
+    if let Err(e) = site.ban_manager.record_success(&ip).await {
+        warn!("Failed to clear failed logins from {ip}: {e}");
+    }
+
+    let session = match site.session_manager.create(&user.username).await {
+        Ok(session) => session,
+        Err(e) => {
+            warn!("Failed to create session: {e}");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    info!("{} logged in from {ip}", user.username);
+
+    let cookie = session_cookie(&site, &site.session_manager.cookie_value(&session));
+    let headers = [
+        (header::SET_COOKIE, cookie),
+        (header::LOCATION, safe_next(next).to_owned()),
+    ];
+
+    (StatusCode::SEE_OTHER, headers).into_response()
+}
+
+// A form rather than a link, so other sites cannot log people out
+pub async fn logout_handler(
+    Extension(site): Extension<Arc<Site>>,
+    headers: HeaderMap,
+    Form(form): Form<LogoutForm>,
+) -> impl IntoResponse {
+    if let Some(cookie) = get_cookie(&headers, SESSION_COOKIE) {
+        match site.session_manager.get(cookie).await {
+            Ok(Some(session)) => {
+                if !constant_time_eq(form.csrf_token.as_bytes(), session.csrf_token.as_bytes()) {
+                    return StatusCode::FORBIDDEN.into_response();
+                }
+
+                if let Err(e) = site.session_manager.delete(&session.session_id).await {
+                    warn!("Failed to delete session: {e}");
+                    return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+                }
+
+                info!("{} logged out", session.username);
+            }
+            Ok(None) => {}
+            Err(e) => {
+                warn!("Failed to look up session: {e}");
+                return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+            }
+        }
+    }
+
+    let headers = [
+        (header::SET_COOKIE, session_cookie(&site, "")),
+        (header::LOCATION, "/".to_owned()),
+    ];
+
+    (StatusCode::SEE_OTHER, headers).into_response()
+}
+
+// The login form again, saying why it will not work for now
+async fn banned_response(site: &Site, next: Option<&str>, until: i64) -> Response {
+    let retry_after = (until - timestamp()).max(0);
+    let minutes = (retry_after + 59) / 60;
+
+    // TODO: i18n
+    let error = format!("Too many failed attempts, try again in {minutes} minute(s)");
+    let mut response =
+        login_response(site, StatusCode::TOO_MANY_REQUESTS, next, Some(&error)).await;
+
+    response
+        .headers_mut()
+        .insert(header::RETRY_AFTER, retry_after.into());
+
+    response
+}
+
+async fn login_response(
+    site: &Site,
+    status: StatusCode,
+    next: Option<&str>,
+    error: Option<&str>,
+) -> Response {
+    let text = match login_impl(site, next, error).await {
+        Ok(text) => text,
+        Err(e) => {
+            warn!("Failed to handle request for /login: {e}");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    let headers = [(header::CONTENT_TYPE, "text/html")];
+
+    (status, headers, text).into_response()
+}
+
+async fn login_impl(site: &Site, next: Option<&str>, error: Option<&str>) -> Result<String> {
+    let mut context = site.base_context();
+    context.insert("title", "Login"); // TODO: i18n
+    context.insert("next", safe_next(next));
+    context.insert("error", &error);
+
+    let rendered = site.render("login.html", &context)?;
+    let minified = minify_html(rendered)?;
+
+    Ok(minified)
+}
+
+// Only our own pages, or the login form would redirect anywhere it is told to
+fn safe_next(next: Option<&str>) -> &str {
+    match next {
+        Some(next) if next.starts_with('/') && !next.starts_with("//") && !next.contains('\\') => {
+            next
+        }
+        _ => "/editor",
+    }
+}
@@ -0,0 +1,115 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use axum::{
+    body::Bytes,
+    extract::Path,
+    http::{header, StatusCode},
+    response::IntoResponse,
+    Extension,
+};
+use log::warn;
+
+use crate::{media_manager::MediaFile, site::Site, util::*};
+
+mod proto {
+    include!(concat!(env!("OUT_DIR"), "/blog.media.rs"));
+}
+
+use proto::{Media, MediaList, UploadRequest};
+
+pub const MAX_UPLOAD_SIZE: usize = 32 * 1024 * 1024;
+
+pub async fn media_handler(
+    Extension(site): Extension<Arc<Site>>,
+    Path(name): Path<String>,
+) -> impl IntoResponse {
+    let (data, mime_type) = match site.media_manager.load(&name).await {
+        Ok(Some(media)) => media,
+        Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+        Err(e) => {
+            warn!("Failed to load media {name}: {e}");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    let headers = [
+        (header::CONTENT_TYPE, mime_type),
+        // Names are content hashes, so a file never changes
+        (header::CACHE_CONTROL, "public, max-age=31536000, immutable"),
+        // Uploaded files must not run scripts on our origin, e.g. an SVG opened directly
+        (
+            header::CONTENT_SECURITY_POLICY,
+            "default-src 'none'; sandbox",
+        ),
+        (header::X_CONTENT_TYPE_OPTIONS, "nosniff"),
+    ];
+
+    (StatusCode::OK, headers, data).into_response()
+}
+
+pub async fn media_upload_handler(
+    Extension(site): Extension<Arc<Site>>,
+    body: Bytes,
+) -> impl IntoResponse {
+    let response = match media_upload_impl(site, body.to_vec()).await {
+        Ok(response) => response,
+        Err(e) => {
+            warn!("Failed to upload media: {e}");
+            return error_status(&e).into_response();
+        }
+    };
+
+    let headers = [("Content-Type", "application/octet-stream")];
+    (StatusCode::CREATED, headers, response).into_response()
+}
+
+pub async fn media_list_handler(Extension(site): Extension<Arc<Site>>) -> impl IntoResponse {
+    let response = match media_list_impl(site).await {
+        Ok(response) => response,
+        Err(e) => {
+            warn!("Failed to list media: {e}");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    let headers = [("Content-Type", "application/octet-stream")];
+    (StatusCode::OK, headers, response).into_response()
+}
+
+async fn media_upload_impl(site: Arc<Site>, body: Vec<u8>) -> Result<Vec<u8>> {
+    let request: UploadRequest = protobuf_decode(&body)?;
+
+    let file = site
+        .media_manager
+        .store(&request.name, &request.data)
+        .await?;
+
+    let response = protobuf_encode(to_proto(file))?;
+
+    Ok(response)
+}
+
+async fn media_list_impl(site: Arc<Site>) -> Result<Vec<u8>> {
+    let media = site
+        .media_manager
+        .list()
+        .await?
+        .into_iter()
+        .map(to_proto)
+        .collect();
+
+    let response = protobuf_encode(MediaList { media })?;
+
+    Ok(response)
+}
+
+fn to_proto(file: MediaFile) -> Media {
+    Media {
+        url: format!("/media/{}", file.name),
+        name: file.name,
+        mime_type: file.mime_type.to_owned(),
+        size: file.size,
+        created: file.created,
+    }
+}
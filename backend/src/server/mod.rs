@@ -1,11 +1,27 @@
 mod archive;
 mod article;
+mod article_api;
 mod asset;
 mod auth;
+mod ban;
+mod dev;
 mod editor;
+mod export;
+mod feed;
 mod index;
+mod login;
+mod media;
+mod page;
 mod request_logger;
 mod search;
+#[allow(clippy::module_inception)]
 mod server;
+mod sitemap;
+mod tag;
+mod token;
+mod token_auth;
+mod two_factor;
+mod user;
 
+pub use export::export_site;
 pub use server::serve;
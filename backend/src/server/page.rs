@@ -0,0 +1,62 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use axum::{
+    extract::Path,
+    http::{header, StatusCode},
+    response::IntoResponse,
+    Extension,
+};
+use log::warn;
+
+use entity::article::Model as Article;
+
+use crate::{
+    article_manager::{ArticleKind, Audience},
+    site::Site,
+    util::*,
+};
+
+pub async fn page_handler(
+    Extension(site): Extension<Arc<Site>>,
+    Path(url): Path<String>,
+) -> impl IntoResponse {
+    let page = match page_impl(site, &url).await {
+        Ok(Some(page)) => page,
+        Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+        Err(e) => {
+            warn!("Failed to handle request for page {url}: {e}");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    let headers = [(header::CONTENT_TYPE, "text/html")];
+
+    (StatusCode::OK, headers, page).into_response()
+}
+
+pub async fn page_impl(site: Arc<Site>, url: &str) -> Result<Option<String>> {
+    let page = site
+        .article_manager
+        .get_article(url, Audience::Public)
+        .await?;
+    let page = match page {
+        Some(page) if ArticleKind::of(&page) == ArticleKind::Page => page,
+        _ => return Ok(None),
+    };
+
+    render_page(&site, page).await.map(Some)
+}
+
+pub async fn render_page(site: &Site, page: Article) -> Result<String> {
+    let content = site.media_manager.responsive_html(&page.content).await?;
+
+    let mut context = site.base_context();
+    context.insert("title", &page.title);
+    context.insert("content", &content);
+
+    let rendered = site.render("page.html", &context)?;
+    let minified = minify_html(rendered)?;
+
+    Ok(minified)
+}
@@ -1,10 +1,28 @@
 use std::sync::Arc;
 
 use anyhow::Result;
-use axum::{body::Bytes, http::StatusCode, response::IntoResponse, Extension};
+use axum::{
+    body::Bytes,
+    extract::Query,
+    http::{header, StatusCode},
+    response::IntoResponse,
+    Extension,
+};
 use log::warn;
+use serde::{Deserialize, Serialize};
 
-use crate::{site::Site, util::*};
+use crate::{article_manager::Audience, site::Site, util::*};
+
+const PROTOCOL_VERSION: u32 = 2;
+const DEFAULT_LIMIT: usize = 10;
+const MAX_LIMIT: usize = 50;
+const RESULTS_PER_PAGE: usize = 10;
+
+#[derive(Deserialize)]
+pub struct SearchPageQuery {
+    q: Option<String>,
+    page: Option<usize>,
+}
 
 pub async fn search_handler(
     Extension(site): Extension<Arc<Site>>,
@@ -22,23 +40,119 @@ pub async fn search_handler(
     (StatusCode::OK, headers, response).into_response()
 }
 
+// Server rendered results for visitors without JavaScript
+pub async fn search_page_handler(
+    Extension(site): Extension<Arc<Site>>,
+    Query(query): Query<SearchPageQuery>,
+) -> impl IntoResponse {
+    let text = match search_page_impl(site, query).await {
+        Ok(text) => text,
+        Err(e) => {
+            warn!("Failed to handle request for /search: {e}");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    let headers = [(header::CONTENT_TYPE, "text/html")];
+
+    (StatusCode::OK, headers, text).into_response()
+}
+
+async fn search_page_impl(site: Arc<Site>, query: SearchPageQuery) -> Result<String> {
+    #[derive(Serialize)]
+    struct Hit {
+        title: String,
+        url: String,
+        snippet: String,
+        created: String,
+    }
+
+    let q = query.q.unwrap_or_default();
+    let page = query.page.unwrap_or(1).max(1) - 1; // 1-indexed to 0-indexed
+
+    let hits = site
+        .article_manager
+        .search(&q, None, Audience::Public)
+        .await?;
+    let total = hits.len();
+
+    let hits: Vec<_> = hits
+        .into_iter()
+        .skip(page * RESULTS_PER_PAGE)
+        .take(RESULTS_PER_PAGE)
+        .map(|hit| Hit {
+            title: hit.article.title,
+            url: hit.article.url,
+            snippet: hit.snippet,
+            created: site.format_time(hit.article.created),
+        })
+        .collect();
+
+    let mut context = site.base_context();
+    context.insert("title", "Search"); // TODO: i18n
+    context.insert("query", &q);
+    context.insert("hits", &hits);
+    context.insert("total", &total);
+
+    if total > (page + 1) * RESULTS_PER_PAGE {
+        context.insert("next", &(page + 2));
+    }
+
+    if page > 0 {
+        context.insert("prev", &page);
+    }
+
+    let rendered = site.render("search.html", &context)?;
+    let minified = minify_html(rendered)?;
+
+    Ok(minified)
+}
+
 async fn search_impl(site: Arc<Site>, body: Vec<u8>) -> Result<Vec<u8>> {
     mod proto {
         include!(concat!(env!("OUT_DIR"), "/blog.search.rs"));
     }
 
-    use proto::{SearchRequest, SearchResponse};
+    use proto::{SearchHit, SearchRequest, SearchResponse};
 
     let request: SearchRequest = protobuf_decode(&body)?;
-    let result: Vec<_> = site
+
+    let limit = match request.limit as usize {
+        0 => DEFAULT_LIMIT,
+        limit => limit.min(MAX_LIMIT),
+    };
+
+    let hits = site
         .article_manager
-        .search(&request.query)
-        .await?
+        .search(&request.query, request.tag.as_deref(), Audience::Public)
+        .await?;
+
+    let total = hits.len() as u32;
+
+    let hits: Vec<_> = hits
         .into_iter()
-        .map(|a| a.title)
+        .skip(request.offset as usize)
+        .take(limit)
+        .map(|hit| SearchHit {
+            url: hit.article.url,
+            title: hit.article.title,
+            excerpt: hit.article.excerpt,
+            snippet: hit.snippet,
+            created: hit.article.created,
+            score: hit.score,
+        })
         .collect();
 
-    let response = SearchResponse { result };
+    // Version 1 clients only understand the list of titles
+    let result = hits.iter().map(|hit| hit.title.clone()).collect();
+
+    let response = SearchResponse {
+        result,
+        version: PROTOCOL_VERSION,
+        hits,
+        total,
+    };
+
     let response = protobuf_encode(response)?;
 
     Ok(response)
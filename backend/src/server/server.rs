@@ -1,39 +1,132 @@
 use std::{net::SocketAddr, sync::Arc};
 
 use anyhow::Result;
-use axum::{extract::Extension, middleware, routing::get, Router};
+use axum::{
+    extract::{DefaultBodyLimit, Extension},
+    middleware,
+    routing::{get, post},
+    Router,
+};
 use log::debug;
 use tokio::net::TcpListener;
 
 use crate::{
     server::{
         archive::archive_handler,
-        article::article_handler,
+        article::{article_handler, article_preview_handler},
+        article_api::{
+            article_create_handler, article_delete_handler, article_fetch_handler,
+            article_update_handler, revision_diff_handler, revision_fetch_handler,
+            revision_list_handler, revision_restore_handler,
+        },
         asset::asset_handler,
         auth::Auth,
+        ban::{ban_lift_handler, bans_handler, purge_bans},
+        dev::{reload_handler, watch_theme},
         editor::editor_handler,
+        feed::{atom_handler, rss_handler},
         index::{index_handler, index_page_handler},
+        login::{login_handler, login_page_handler, logout_handler},
+        media::{media_handler, media_list_handler, media_upload_handler, MAX_UPLOAD_SIZE},
+        page::page_handler,
         request_logger::request_logger,
-        search::search_handler,
+        search::{search_handler, search_page_handler},
+        sitemap::{robots_handler, sitemap_handler},
+        tag::{tag_handler, tag_page_handler, tags_handler},
+        token::{token_create_handler, token_revoke_handler, tokens_handler},
+        token_auth::TokenAuth,
+        two_factor::{two_factor_disable_handler, two_factor_enable_handler, two_factor_handler},
+        user::{user_create_handler, user_delete_handler, user_update_handler, users_handler},
     },
     site::Site,
 };
 
 pub async fn serve(bind: SocketAddr, site: Site) -> Result<()> {
+    let site = Arc::new(site);
+
     let editor = Router::new()
         .route("/editor", get(editor_handler))
-        .layer(Auth::new(&site.admin.0, &site.admin.1));
+        .route("/editor/preview/:url", get(article_preview_handler))
+        .route(
+            "/editor/2fa",
+            get(two_factor_handler).post(two_factor_enable_handler),
+        )
+        .route("/editor/2fa/disable", post(two_factor_disable_handler))
+        .route(
+            "/editor/users",
+            get(users_handler).post(user_create_handler),
+        )
+        .route("/editor/users/:id", post(user_update_handler))
+        .route("/editor/users/:id/delete", post(user_delete_handler))
+        .route("/editor/bans", get(bans_handler))
+        .route("/editor/bans/lift", post(ban_lift_handler))
+        .route(
+            "/editor/tokens",
+            get(tokens_handler).post(token_create_handler),
+        )
+        .route("/editor/tokens/:id/revoke", post(token_revoke_handler))
+        .route("/api/article", post(article_create_handler))
+        .route(
+            "/api/article/:url",
+            get(article_fetch_handler)
+                .put(article_update_handler)
+                .delete(article_delete_handler),
+        )
+        .route("/api/article/:url/revisions", get(revision_list_handler))
+        .route(
+            "/api/article/:url/revisions/:id",
+            get(revision_fetch_handler),
+        )
+        .route(
+            "/api/article/:url/revisions/:id/restore",
+            post(revision_restore_handler),
+        )
+        .route("/api/article/:url/diff", get(revision_diff_handler))
+        .route(
+            "/api/media",
+            get(media_list_handler)
+                .post(media_upload_handler)
+                .layer(DefaultBodyLimit::max(MAX_UPLOAD_SIZE)),
+        )
+        .layer(Auth::new(site.clone()))
+        // Outside of `Auth`, which lets through whatever it let in
+        .layer(TokenAuth::new(site.clone()));
+
+    tokio::spawn(purge_bans(site.clone()));
+
+    let dev = match site.is_dev() {
+        true => {
+            tokio::spawn(watch_theme(site.clone()));
+            Router::new().route("/dev/reload", get(reload_handler))
+        }
+        false => Router::new(),
+    };
 
     let app = Router::new()
         .merge(editor)
+        .merge(dev)
         .route("/", get(index_handler))
+        .route("/login", get(login_page_handler).post(login_handler))
+        .route("/logout", post(logout_handler))
         .route("/archive", get(archive_handler))
         .route("/page/:page", get(index_page_handler))
-        .route("/api/search", get(search_handler))
+        .route("/search", get(search_page_handler))
+        // Browsers cannot send a body with GET, so POST is accepted as well
+        .route("/api/search", get(search_handler).post(search_handler))
+        .route("/sitemap.xml", get(sitemap_handler))
+        .route("/robots.txt", get(robots_handler))
+        .route("/feed.xml", get(rss_handler))
+        .route("/atom.xml", get(atom_handler))
+        .route("/tags", get(tags_handler))
+        .route("/tag/:tag", get(tag_handler))
+        .route("/tag/:tag/page/:page", get(tag_page_handler))
         .route("/article/:url", get(article_handler))
         .route("/asset/*path", get(asset_handler))
+        .route("/media/*path", get(media_handler))
+        // Standalone pages, any other top level route takes precedence
+        .route("/:url", get(page_handler))
         .layer(middleware::from_fn(request_logger))
-        .layer(Extension(Arc::new(site)));
+        .layer(Extension(site));
 
     debug!("Listening on {bind}");
     let socket = TcpListener::bind(bind).await?;
@@ -0,0 +1,108 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use axum::{
+    http::{header, StatusCode},
+    response::IntoResponse,
+    Extension,
+};
+use chrono::{DateTime, Utc};
+use log::warn;
+use serde::Serialize;
+
+use crate::{article_manager::Audience, server::index::ARTICLES_PER_PAGE, site::Site, util::*};
+
+pub async fn sitemap_handler(Extension(site): Extension<Arc<Site>>) -> impl IntoResponse {
+    let text = match sitemap_impl(site).await {
+        Ok(text) => text,
+        Err(e) => {
+            warn!("Failed to generate sitemap: {e}");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    let headers = [(header::CONTENT_TYPE, "application/xml")];
+
+    (StatusCode::OK, headers, text).into_response()
+}
+
+pub async fn robots_handler(Extension(site): Extension<Arc<Site>>) -> impl IntoResponse {
+    let headers = [(header::CONTENT_TYPE, "text/plain")];
+
+    (StatusCode::OK, headers, site.get_robots_txt()).into_response()
+}
+
+pub async fn sitemap_impl(site: Arc<Site>) -> Result<String> {
+    #[derive(Serialize)]
+    struct Url {
+        loc: String,
+        lastmod: Option<String>,
+    }
+
+    let base_url = site.get_base_url();
+
+    let metadata = site
+        .article_manager
+        .get_all_article_metadatas(Audience::Public)
+        .await?;
+    let tags = site.article_manager.get_tags(Audience::Public).await?;
+    let page_metadata = site
+        .article_manager
+        .get_page_metadatas(Audience::Public)
+        .await?;
+
+    let mut urls = vec![Url {
+        loc: format!("{base_url}/"),
+        lastmod: None,
+    }];
+
+    let pages = metadata.len().div_ceil(ARTICLES_PER_PAGE);
+    for page in 2..=pages {
+        urls.push(Url {
+            loc: format!("{base_url}/page/{page}"),
+            lastmod: None,
+        });
+    }
+
+    urls.push(Url {
+        loc: format!("{base_url}/archive"),
+        lastmod: None,
+    });
+
+    urls.push(Url {
+        loc: format!("{base_url}/tags"),
+        lastmod: None,
+    });
+
+    for tag in tags {
+        urls.push(Url {
+            loc: format!("{base_url}/tag/{}", urlencode(&tag.tag)),
+            lastmod: None,
+        });
+    }
+
+    for article in metadata {
+        let lastmod = DateTime::<Utc>::from_timestamp(article.last_modified, 0);
+
+        urls.push(Url {
+            loc: format!("{base_url}/article/{}", urlencode(&article.url)),
+            lastmod: lastmod.map(|dt| dt.to_rfc3339()),
+        });
+    }
+
+    for page in page_metadata {
+        let lastmod = DateTime::<Utc>::from_timestamp(page.last_modified, 0);
+
+        urls.push(Url {
+            loc: format!("{base_url}/{}", urlencode(&page.url)),
+            lastmod: lastmod.map(|dt| dt.to_rfc3339()),
+        });
+    }
+
+    let mut context = site.base_context();
+    context.insert("urls", &urls);
+
+    let rendered = site.render("sitemap.xml", &context)?;
+
+    Ok(rendered)
+}
@@ -0,0 +1,88 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use axum::{
+    extract::Path,
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    Extension,
+};
+use log::warn;
+
+use crate::{article_manager::Audience, server::index::render_article_list, site::Site, util::*};
+
+pub async fn tag_handler(
+    Extension(site): Extension<Arc<Site>>,
+    Path(tag): Path<String>,
+) -> impl IntoResponse {
+    tag_page_response(site, &tag, 1).await
+}
+
+pub async fn tag_page_handler(
+    Extension(site): Extension<Arc<Site>>,
+    Path((tag, page)): Path<(String, usize)>,
+) -> impl IntoResponse {
+    tag_page_response(site, &tag, page).await
+}
+
+pub async fn tags_handler(Extension(site): Extension<Arc<Site>>) -> impl IntoResponse {
+    let text = match tags_impl(site).await {
+        Ok(text) => text,
+        Err(e) => {
+            warn!("Failed to handle request for /tags: {e}");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    let headers = [(header::CONTENT_TYPE, "text/html")];
+
+    (StatusCode::OK, headers, text).into_response()
+}
+
+async fn tag_page_response(site: Arc<Site>, tag: &str, page: usize) -> Response {
+    let text = match tag_impl(site, tag, page).await {
+        Ok(Some(text)) => text,
+        Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+        Err(e) => {
+            warn!("Failed to handle request for /tag/{tag}: {e}");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    let headers = [(header::CONTENT_TYPE, "text/html")];
+
+    (StatusCode::OK, headers, text).into_response()
+}
+
+pub async fn tag_impl(site: Arc<Site>, tag: &str, page: usize) -> Result<Option<String>> {
+    let metadata = site
+        .article_manager
+        .get_article_metadatas_by_tag(tag, Audience::Public)
+        .await?;
+
+    if metadata.is_empty() {
+        return Ok(None);
+    }
+
+    let mut context = site.base_context();
+    context.insert("title", tag);
+    context.insert("tag", tag);
+
+    let page_prefix = format!("/tag/{}/page", urlencode(tag));
+    let rendered = render_article_list(&site, context, metadata, page, &page_prefix)?;
+
+    Ok(Some(rendered))
+}
+
+pub async fn tags_impl(site: Arc<Site>) -> Result<String> {
+    let tags = site.article_manager.get_tags(Audience::Public).await?;
+
+    let mut context = site.base_context();
+    context.insert("tags", &tags);
+    context.insert("title", "Tags"); // TODO: i18n
+
+    let rendered = site.render("tags.html", &context)?;
+    let minified = minify_html(rendered)?;
+
+    Ok(minified)
+}
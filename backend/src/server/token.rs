@@ -0,0 +1,196 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use axum::{
+    extract::Path,
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    Extension, Form,
+};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use tera::Context;
+
+use entity::{session::Model as Session, user::Model as User};
+
+use crate::{site::Site, token_manager::Scope, user_manager::Role, util::*};
+
+// One checkbox per scope, HTML forms repeat the name for multiple values and serde cannot
+// collect those
+#[derive(Deserialize)]
+pub struct TokenForm {
+    name: String,
+    read: Option<String>,
+    articles: Option<String>,
+    media: Option<String>,
+}
+
+pub async fn tokens_handler(
+    Extension(site): Extension<Arc<Site>>,
+    Extension(user): Extension<User>,
+    session: Option<Extension<Session>>,
+) -> impl IntoResponse {
+    let context = site.base_context();
+    render(
+        &site,
+        StatusCode::OK,
+        &user,
+        session.as_ref().map(|s| &s.0),
+        context,
+    )
+    .await
+}
+
+pub async fn token_create_handler(
+    Extension(site): Extension<Arc<Site>>,
+    Extension(user): Extension<User>,
+    session: Option<Extension<Session>>,
+    Form(form): Form<TokenForm>,
+) -> impl IntoResponse {
+    let scopes: Vec<_> = [
+        (form.read, Scope::Read),
+        (form.articles, Scope::Articles),
+        (form.media, Scope::Media),
+    ]
+    .into_iter()
+    .filter_map(|(checked, scope)| checked.map(|_| scope))
+    .collect();
+
+    let mut context = site.base_context();
+
+    // Shown once, only the hash is kept
+    let status = match site
+        .token_manager
+        .create_token(user.user_id, &form.name, &scopes)
+        .await
+    {
+        Ok((_, secret)) => {
+            context.insert("new_token", &secret);
+            StatusCode::CREATED
+        }
+        Err(e) => {
+            context.insert("error", &e.to_string());
+            StatusCode::BAD_REQUEST
+        }
+    };
+
+    render(
+        &site,
+        status,
+        &user,
+        session.as_ref().map(|s| &s.0),
+        context,
+    )
+    .await
+}
+
+pub async fn token_revoke_handler(
+    Extension(site): Extension<Arc<Site>>,
+    Extension(user): Extension<User>,
+    Path(token_id): Path<i64>,
+) -> impl IntoResponse {
+    match token_revoke_impl(&site, &user, token_id).await {
+        Ok(Some(true)) => (
+            StatusCode::SEE_OTHER,
+            [(header::LOCATION, "/editor/tokens")],
+        )
+            .into_response(),
+        Ok(Some(false)) => StatusCode::FORBIDDEN.into_response(),
+        Ok(None) => StatusCode::NOT_FOUND.into_response(),
+        Err(e) => {
+            warn!("Failed to revoke API token {token_id}: {e}");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+// Whether the user may revoke the token, which is then gone. Admins can revoke anyone's.
+async fn token_revoke_impl(site: &Site, user: &User, token_id: i64) -> Result<Option<bool>> {
+    let token = match site.token_manager.get_token(token_id).await? {
+        Some(token) => token,
+        None => return Ok(None),
+    };
+
+    if token.user_id != user.user_id && Role::of(user) != Role::Admin {
+        return Ok(Some(false));
+    }
+
+    site.token_manager.revoke_token(token_id).await?;
+
+    Ok(Some(true))
+}
+
+async fn render(
+    site: &Site,
+    status: StatusCode,
+    user: &User,
+    session: Option<&Session>,
+    context: Context,
+) -> Response {
+    let text = match tokens_impl(site, user, session, context).await {
+        Ok(text) => text,
+        Err(e) => {
+            warn!("Failed to handle request for /editor/tokens: {e}");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    let headers = [(header::CONTENT_TYPE, "text/html")];
+
+    (status, headers, text).into_response()
+}
+
+async fn tokens_impl(
+    site: &Site,
+    user: &User,
+    session: Option<&Session>,
+    mut context: Context,
+) -> Result<String> {
+    #[derive(Serialize)]
+    struct TokenItem {
+        token_id: i64,
+        name: String,
+        // Only filled in for admins, who see everyone's tokens
+        owner: Option<String>,
+        scopes: Vec<Scope>,
+        created: String,
+        last_used: Option<String>,
+    }
+
+    let admin = Role::of(user) == Role::Admin;
+    let user_id = match admin {
+        true => None,
+        false => Some(user.user_id),
+    };
+
+    let mut tokens = Vec::new();
+
+    for token in site.token_manager.get_tokens(user_id).await? {
+        let owner = match admin {
+            true => site
+                .user_manager
+                .get_user(token.user_id)
+                .await?
+                .map(|owner| owner.username),
+            false => None,
+        };
+
+        tokens.push(TokenItem {
+            owner,
+            scopes: Scope::of(&token),
+            created: site.format_time(token.created),
+            last_used: token.last_used.map(|last_used| site.format_time(last_used)),
+            token_id: token.token_id,
+            name: token.name,
+        });
+    }
+
+    context.insert("title", "API tokens"); // TODO: i18n
+    context.insert("csrf_token", &session.map(|s| &s.csrf_token));
+    context.insert("tokens", &tokens);
+
+    let rendered = site.render("tokens.html", &context)?;
+    let minified = minify_html(rendered)?;
+
+    Ok(minified)
+}
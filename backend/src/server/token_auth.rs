@@ -0,0 +1,138 @@
+use std::{
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use anyhow::Result;
+use axum::{
+    body::Body,
+    extract::Request,
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+};
+use futures_util::future::BoxFuture;
+use log::{info, warn};
+use tower::{Layer, Service};
+
+use crate::{site::Site, token_manager::Scope};
+
+// Lets scripts in with `Authorization: Bearer <token>`, acting as the user the token belongs to
+// and only within its scopes. Requests without one are left to `Auth`, which sits inside.
+#[derive(Clone)]
+pub struct TokenAuth {
+    site: Arc<Site>,
+}
+
+#[derive(Clone)]
+pub struct TokenAuthMiddleware<S> {
+    inner: S,
+    site: Arc<Site>,
+}
+
+impl TokenAuth {
+    pub fn new(site: Arc<Site>) -> Self {
+        Self { site }
+    }
+}
+
+impl<S> Layer<S> for TokenAuth {
+    type Service = TokenAuthMiddleware<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        TokenAuthMiddleware {
+            inner,
+            site: self.site.clone(),
+        }
+    }
+}
+
+impl<S> Service<Request> for TokenAuthMiddleware<S>
+where
+    S: Service<Request, Response = Response> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request) -> Self::Future {
+        let site = self.site.clone();
+
+        // The service that was polled ready is the one to call, a fresh clone takes its place
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+
+        Box::pin(async move {
+            match authenticate(&site, request).await {
+                Ok(Ok(request)) => inner.call(request).await,
+                Ok(Err(response)) => Ok(response),
+                Err(e) => {
+                    warn!("Failed to authenticate request with token: {e}");
+                    Ok(StatusCode::INTERNAL_SERVER_ERROR.into_response())
+                }
+            }
+        })
+    }
+}
+
+// Either the request to pass on, with the token and its user in its extensions if it had a
+// token, or the response to turn it away with
+async fn authenticate(site: &Site, mut request: Request) -> Result<Result<Request, Response>> {
+    const METHOD: &str = "Bearer ";
+
+    let secret = match request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix(METHOD))
+    {
+        Some(secret) => secret.trim().to_owned(),
+        None => return Ok(Ok(request)),
+    };
+
+    let url = request.uri().path().to_owned();
+
+    let token = match site.token_manager.use_token(&secret).await? {
+        Some(token) => token,
+        None => {
+            info!("Rejected request to {url} with an unknown token");
+            return Ok(Err(unauthorized()));
+        }
+    };
+
+    let allowed = Scope::required(request.method(), &url)
+        .is_some_and(|scope| Scope::of(&token).contains(&scope));
+
+    if !allowed {
+        info!(
+            "Token {} is not allowed to {} {url}",
+            token.name,
+            request.method()
+        );
+        return Ok(Err(StatusCode::FORBIDDEN.into_response()));
+    }
+
+    // Tokens are revoked along with their user, so this is only a safety net
+    let user = match site.user_manager.get_user(token.user_id).await? {
+        Some(user) => user,
+        None => return Ok(Err(unauthorized())),
+    };
+
+    // Browsers never send the header on their own, so no CSRF token is needed
+    request.extensions_mut().insert(user);
+    request.extensions_mut().insert(token);
+
+    Ok(Ok(request))
+}
+
+fn unauthorized() -> Response<Body> {
+    Response::builder()
+        .status(StatusCode::UNAUTHORIZED)
+        .header(header::WWW_AUTHENTICATE, "Bearer")
+        .body(Body::empty())
+        .unwrap()
+}
@@ -0,0 +1,164 @@
+use std::sync::Arc;
+
+use anyhow::{anyhow, Result};
+use axum::{
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    Extension, Form,
+};
+use log::{info, warn};
+use qrcode::{render::svg, QrCode};
+use serde::Deserialize;
+use tera::Context;
+
+use entity::{session::Model as Session, user::Model as User};
+
+use crate::{site::Site, totp, two_factor, util::*};
+
+#[derive(Deserialize)]
+pub struct CodeForm {
+    code: String,
+}
+
+// Settings page of the logged in user, with a secret to scan while two-factor is off
+pub async fn two_factor_handler(
+    Extension(site): Extension<Arc<Site>>,
+    Extension(user): Extension<User>,
+    session: Option<Extension<Session>>,
+) -> impl IntoResponse {
+    let mut context = site.base_context();
+    let result = two_factor_impl(&site, &mut context, &user, session.as_ref().map(|s| &s.0)).await;
+
+    render(&site, StatusCode::OK, result.map(|_| context))
+}
+
+pub async fn two_factor_enable_handler(
+    Extension(site): Extension<Arc<Site>>,
+    Extension(user): Extension<User>,
+    session: Option<Extension<Session>>,
+    Form(form): Form<CodeForm>,
+) -> impl IntoResponse {
+    let codes =
+        match two_factor::finish_enrollment(&site.user_manager, &user, &form.code, timestamp())
+            .await
+        {
+            Ok(codes) => codes,
+            Err(e) => {
+                warn!("Failed to enable two-factor authentication: {e}");
+                return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+            }
+        };
+
+    let mut context = site.base_context();
+
+    // TODO: i18n
+    let status = match codes {
+        Some(codes) => {
+            info!("Two-factor authentication enabled for {}", user.username);
+            context.insert("recovery_codes", &codes);
+            StatusCode::OK
+        }
+        None => {
+            context.insert("error", "Wrong code, try again");
+            StatusCode::BAD_REQUEST
+        }
+    };
+
+    let result = two_factor_impl(&site, &mut context, &user, session.as_ref().map(|s| &s.0)).await;
+
+    render(&site, status, result.map(|_| context))
+}
+
+pub async fn two_factor_disable_handler(
+    Extension(site): Extension<Arc<Site>>,
+    Extension(user): Extension<User>,
+    session: Option<Extension<Session>>,
+    Form(form): Form<CodeForm>,
+) -> impl IntoResponse {
+    let mut context = site.base_context();
+
+    let status = match disable_impl(&site, &user, &form.code).await {
+        Ok(true) => StatusCode::OK,
+        Ok(false) => {
+            context.insert("error", "Wrong code, two-factor authentication is still on");
+            StatusCode::BAD_REQUEST
+        }
+        Err(e) => {
+            warn!("Failed to disable two-factor authentication: {e}");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    let result = two_factor_impl(&site, &mut context, &user, session.as_ref().map(|s| &s.0)).await;
+
+    render(&site, status, result.map(|_| context))
+}
+
+// Only with a valid code, so a forgotten open session is not enough to turn it off
+async fn disable_impl(site: &Site, user: &User, code: &str) -> Result<bool> {
+    if !two_factor::verify(&site.user_manager, user, code, timestamp()).await? {
+        return Ok(false);
+    }
+
+    two_factor::disable(&site.user_manager, user).await?;
+    info!("Two-factor authentication disabled for {}", user.username);
+
+    Ok(true)
+}
+
+async fn two_factor_impl(
+    site: &Site,
+    context: &mut Context,
+    user: &User,
+    session: Option<&Session>,
+) -> Result<()> {
+    context.insert("title", "Two-factor authentication"); // TODO: i18n
+    context.insert("csrf_token", &session.map(|s| &s.csrf_token));
+
+    // The one from the request is from before any change the handler made
+    let user = site
+        .user_manager
+        .get_user(user.user_id)
+        .await?
+        .ok_or(anyhow!("user {} no longer exists", user.username))?;
+
+    let enabled = two_factor::is_enabled(&user);
+    context.insert("enabled", &enabled);
+
+    if enabled {
+        return Ok(());
+    }
+
+    let secret = two_factor::begin_enrollment(&site.user_manager, &user).await?;
+    let uri = totp::otpauth_uri(site.get_site_name().as_str(), &user.username, &secret);
+
+    let qr_code = QrCode::new(uri.as_bytes())?
+        .render::<svg::Color>()
+        .min_dimensions(200, 200)
+        .build();
+
+    context.insert("secret", &secret);
+    context.insert("uri", &uri);
+    context.insert("qr_code", &qr_code);
+
+    Ok(())
+}
+
+fn render(site: &Site, status: StatusCode, context: Result<Context>) -> Response {
+    let text = context.and_then(|context| {
+        let rendered = site.render("two_factor.html", &context)?;
+        minify_html(rendered)
+    });
+
+    let text = match text {
+        Ok(text) => text,
+        Err(e) => {
+            warn!("Failed to handle request for /editor/2fa: {e}");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    let headers = [(header::CONTENT_TYPE, "text/html")];
+
+    (status, headers, text).into_response()
+}
@@ -0,0 +1,193 @@
+use std::sync::Arc;
+
+use anyhow::{anyhow, Result};
+use axum::{
+    extract::Path,
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    Extension, Form,
+};
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+use entity::{session::Model as Session, user::Model as User};
+
+use crate::{
+    site::Site,
+    user_manager::{Role, UserFields},
+    util::*,
+};
+
+#[derive(Deserialize)]
+pub struct UserForm {
+    username: String,
+    display_name: String,
+    // Empty leaves the password alone when updating, and the field is left out for the account
+    // from the config
+    #[serde(default)]
+    password: String,
+    role: String,
+}
+
+pub async fn users_handler(
+    Extension(site): Extension<Arc<Site>>,
+    Extension(user): Extension<User>,
+    session: Option<Extension<Session>>,
+) -> impl IntoResponse {
+    if Role::of(&user) != Role::Admin {
+        return StatusCode::FORBIDDEN.into_response();
+    }
+
+    render(&site, StatusCode::OK, session.as_ref().map(|s| &s.0), None).await
+}
+
+pub async fn user_create_handler(
+    Extension(site): Extension<Arc<Site>>,
+    Extension(user): Extension<User>,
+    session: Option<Extension<Session>>,
+    Form(form): Form<UserForm>,
+) -> impl IntoResponse {
+    if Role::of(&user) != Role::Admin {
+        return StatusCode::FORBIDDEN.into_response();
+    }
+
+    let result = match to_fields(form) {
+        Ok(fields) => site.user_manager.create_user(fields).await.map(|_| ()),
+        Err(e) => Err(e),
+    };
+
+    respond(&site, session.as_ref().map(|s| &s.0), result).await
+}
+
+pub async fn user_update_handler(
+    Extension(site): Extension<Arc<Site>>,
+    Extension(user): Extension<User>,
+    session: Option<Extension<Session>>,
+    Path(user_id): Path<i64>,
+    Form(form): Form<UserForm>,
+) -> impl IntoResponse {
+    if Role::of(&user) != Role::Admin {
+        return StatusCode::FORBIDDEN.into_response();
+    }
+
+    let result = match to_fields(form) {
+        Ok(fields) => site.user_manager.update_user(user_id, fields).await,
+        Err(e) => Err(e),
+    };
+
+    match result {
+        Ok(Some(_)) => respond(&site, session.as_ref().map(|s| &s.0), Ok(())).await,
+        Ok(None) => StatusCode::NOT_FOUND.into_response(),
+        Err(e) => respond(&site, session.as_ref().map(|s| &s.0), Err(e)).await,
+    }
+}
+
+pub async fn user_delete_handler(
+    Extension(site): Extension<Arc<Site>>,
+    Extension(user): Extension<User>,
+    session: Option<Extension<Session>>,
+    Path(user_id): Path<i64>,
+) -> impl IntoResponse {
+    if Role::of(&user) != Role::Admin {
+        return StatusCode::FORBIDDEN.into_response();
+    }
+
+    match user_delete_impl(&site, user_id).await {
+        Ok(true) => respond(&site, session.as_ref().map(|s| &s.0), Ok(())).await,
+        Ok(false) => StatusCode::NOT_FOUND.into_response(),
+        Err(e) => respond(&site, session.as_ref().map(|s| &s.0), Err(e)).await,
+    }
+}
+
+// Their articles stay, with the site's author shown instead
+async fn user_delete_impl(site: &Site, user_id: i64) -> Result<bool> {
+    if !site.user_manager.delete_user(user_id).await? {
+        return Ok(false);
+    }
+
+    site.article_manager.remove_author(user_id).await?;
+
+    Ok(true)
+}
+
+// Back to the list after a change, so reloading the page does not send the form again.
+// Mistakes in the form are shown above it.
+async fn respond(site: &Site, session: Option<&Session>, result: Result<()>) -> Response {
+    match result {
+        Ok(()) => (StatusCode::SEE_OTHER, [(header::LOCATION, "/editor/users")]).into_response(),
+        Err(e) => render(site, StatusCode::BAD_REQUEST, session, Some(&e.to_string())).await,
+    }
+}
+
+async fn render(
+    site: &Site,
+    status: StatusCode,
+    session: Option<&Session>,
+    error: Option<&str>,
+) -> Response {
+    let text = match users_impl(site, session, error).await {
+        Ok(text) => text,
+        Err(e) => {
+            warn!("Failed to handle request for /editor/users: {e}");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    let headers = [(header::CONTENT_TYPE, "text/html")];
+
+    (status, headers, text).into_response()
+}
+
+async fn users_impl(site: &Site, session: Option<&Session>, error: Option<&str>) -> Result<String> {
+    #[derive(Serialize)]
+    struct UserItem {
+        user_id: i64,
+        username: String,
+        display_name: String,
+        role: Role,
+        // The account from the config, managed there
+        root: bool,
+    }
+
+    let users: Vec<_> = site
+        .user_manager
+        .get_all_users()
+        .await?
+        .into_iter()
+        .map(|user| UserItem {
+            root: site.user_manager.is_root(&user),
+            role: Role::of(&user),
+            user_id: user.user_id,
+            username: user.username,
+            display_name: user.display_name,
+        })
+        .collect();
+
+    let mut context = site.base_context();
+    context.insert("title", "Users"); // TODO: i18n
+    context.insert("csrf_token", &session.map(|s| &s.csrf_token));
+    context.insert("users", &users);
+    context.insert("roles", &Role::ALL);
+    context.insert("error", &error);
+
+    let rendered = site.render("users.html", &context)?;
+    let minified = minify_html(rendered)?;
+
+    Ok(minified)
+}
+
+fn to_fields(form: UserForm) -> Result<UserFields> {
+    let role = Role::parse(&form.role).ok_or(anyhow!("unknown role {}", form.role))?;
+
+    let password = match form.password.is_empty() {
+        true => None,
+        false => Some(form.password),
+    };
+
+    Ok(UserFields {
+        username: Some(form.username.trim().to_owned()),
+        display_name: Some(form.display_name),
+        password,
+        role: Some(role),
+    })
+}
@@ -0,0 +1,124 @@
+use anyhow::{anyhow, Result};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use hmac::{Hmac, Mac};
+use log::info;
+use rand::{rngs::OsRng, RngCore};
+use sea_orm::{ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, Set};
+use sha2::Sha256;
+
+use entity::{
+    prelude::Session as SessionEntity,
+    session::{self, Model as Session},
+};
+
+use crate::util::*;
+
+// A week, logging in again after that is no hardship
+pub const SESSION_LIFETIME: i64 = 60 * 60 * 24 * 7;
+
+pub struct SessionManager {
+    db: DatabaseConnection,
+    secret: Vec<u8>,
+}
+
+impl SessionManager {
+    // Without a configured secret one is made up, which logs everyone out on restart
+    pub fn new(db: DatabaseConnection, secret: Option<&str>) -> Self {
+        let secret = match secret {
+            Some(secret) => secret.as_bytes().to_vec(),
+            None => {
+                info!("No session_secret configured, sessions will not survive a restart");
+                random_bytes(32)
+            }
+        };
+
+        Self { db, secret }
+    }
+
+    pub async fn create(&self, username: &str) -> Result<Session> {
+        let now = timestamp();
+
+        // As good a time as any to forget about old sessions
+        SessionEntity::delete_many()
+            .filter(session::Column::Expires.lt(now))
+            .exec(&self.db)
+            .await?;
+
+        let session_id = URL_SAFE_NO_PAD.encode(random_bytes(32));
+
+        let session = session::ActiveModel {
+            session_id: Set(session_id.clone()),
+            username: Set(username.to_owned()),
+            csrf_token: Set(URL_SAFE_NO_PAD.encode(random_bytes(32))),
+            created: Set(now),
+            expires: Set(now + SESSION_LIFETIME),
+        };
+
+        // The id is not an auto increment column, so there is no id to read back
+        SessionEntity::insert(session)
+            .exec_without_returning(&self.db)
+            .await?;
+
+        SessionEntity::find_by_id(session_id)
+            .one(&self.db)
+            .await?
+            .ok_or(anyhow!("session vanished right after it was created"))
+    }
+
+    // The session a cookie value refers to, if it is genuine and still valid
+    pub async fn get(&self, cookie: &str) -> Result<Option<Session>> {
+        let session_id = match self.verify(cookie) {
+            Some(session_id) => session_id,
+            None => return Ok(None),
+        };
+
+        let session = SessionEntity::find_by_id(session_id)
+            .filter(session::Column::Expires.gte(timestamp()))
+            .one(&self.db)
+            .await?;
+
+        Ok(session)
+    }
+
+    pub async fn delete(&self, session_id: &str) -> Result<()> {
+        SessionEntity::delete_by_id(session_id)
+            .exec(&self.db)
+            .await?;
+
+        Ok(())
+    }
+
+    // `<id>.<signature>`, so a made up id is turned away without asking the database
+    pub fn cookie_value(&self, session: &Session) -> String {
+        let signature = URL_SAFE_NO_PAD.encode(self.sign(&session.session_id));
+        format!("{}.{signature}", session.session_id)
+    }
+
+    fn verify<'a>(&self, cookie: &'a str) -> Option<&'a str> {
+        let (session_id, signature) = cookie.split_once('.')?;
+        let signature = URL_SAFE_NO_PAD.decode(signature).ok()?;
+
+        let mut mac = self.mac();
+        mac.update(session_id.as_bytes());
+        mac.verify_slice(&signature).ok()?;
+
+        Some(session_id)
+    }
+
+    fn sign(&self, session_id: &str) -> Vec<u8> {
+        let mut mac = self.mac();
+        mac.update(session_id.as_bytes());
+        mac.finalize().into_bytes().to_vec()
+    }
+
+    fn mac(&self) -> Hmac<Sha256> {
+        // HMAC takes keys of any length
+        Hmac::new_from_slice(&self.secret).unwrap()
+    }
+}
+
+fn random_bytes(len: usize) -> Vec<u8> {
+    let mut bytes = vec![0; len];
+    OsRng.fill_bytes(&mut bytes);
+    bytes
+}
@@ -1,37 +1,78 @@
-use std::{collections::HashMap, sync::Mutex};
+use std::{collections::HashMap, fmt, net::IpAddr, path::PathBuf, sync::Mutex};
 
 use anyhow::Result;
 use chrono::{Datelike, Local};
 use futures::executor;
 use log::{debug, warn};
-use sea_orm::{ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter};
+use parking_lot::RwLock;
+use sea_orm::{
+    ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, QueryOrder, Set, TransactionTrait,
+};
 use serde::{Serialize, Serializer};
 use tera::{Context, Tera};
+use tokio::sync::broadcast;
 
 use entity::{
+    article::Model as Article,
     metadata::{self, Model as Metadata},
     prelude::Metadata as MetadataEntity,
 };
 
 use crate::{
     article_manager::ArticleManager,
-    config::{Config, Locale},
+    ban_manager::BanManager,
+    config::{Config, Locale, MenuItem},
     db::connect_to_db,
+    media_manager::MediaManager,
+    password::is_hashed,
+    session_manager::SessionManager,
     text::Text,
+    theme::Theme,
+    token_manager::TokenManager,
+    two_factor,
+    user_manager::UserManager,
 };
 
 enum Type {
+    Int(i64),
+    Float(f64),
     String(String),
 }
 
+// Every key is used as text, numbers only get in through imports from elsewhere
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Int(value) => write!(f, "{value}"),
+            Self::Float(value) => write!(f, "{value}"),
+            Self::String(value) => f.write_str(value),
+        }
+    }
+}
+
 // This struct tracks all site wide settings
 pub struct Site {
     pub article_manager: ArticleManager,
+    pub media_manager: MediaManager,
+    pub session_manager: SessionManager,
+    pub user_manager: UserManager,
+    pub ban_manager: BanManager,
+    pub token_manager: TokenManager,
     text: Text,
     db: DatabaseConnection,
     metadata: Mutex<HashMap<&'static str, Type>>,
-    templates: Tera,
-    pub admin: (String, String),
+    theme: RwLock<Theme>,
+    templates: RwLock<Tera>,
+    theme_path: Option<String>,
+    // Only in development mode, told whenever templates or assets were reloaded
+    reload: Option<broadcast::Sender<()>>,
+    base_url: String,
+    robots_txt: Option<String>,
+    menu: Vec<MenuItem>,
+    pub basic_auth: bool,
+    // Set while rendering a static export, which has no backend to search with
+    pub static_export: bool,
+    trusted_proxies: Vec<IpAddr>,
 }
 
 pub enum Str {
@@ -39,6 +80,15 @@ pub enum Str {
     Static(&'static str),
 }
 
+impl Str {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Str::Owned(s) => s,
+            Str::Static(s) => s,
+        }
+    }
+}
+
 impl Serialize for Str {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -53,11 +103,26 @@ impl Serialize for Str {
 
 impl Site {
     const METADATA_KEYS: &'static [&'static str] = &["author", "site_name"];
+    // Who the account from the config was at the last start, to notice when it is renamed
+    const ROOT_KEY: &'static str = "root_username";
 
     pub async fn new(config: &Config) -> Result<Self> {
         let db = connect_to_db(&config.database_path).await?;
 
         let am = ArticleManager::new(db.clone())?;
+        let mm = MediaManager::new(config.media_path.as_deref().unwrap_or("media"), db.clone())?;
+        let sm = SessionManager::new(db.clone(), config.session_secret.as_deref());
+        let um = UserManager::new(db.clone(), &config.username, &config.password).await?;
+        let bm = BanManager::new(db.clone(), config.max_login_attempts, config.ban_duration);
+        let tm = TokenManager::new(db.clone());
+
+        if config.base_url.is_none() {
+            warn!("No base_url configured, feeds and the sitemap will only have relative links");
+        }
+
+        if !is_hashed(&config.password) {
+            warn!("The password in the config is not hashed, replace it with the output of hash-password");
+        }
 
         let text = match config.locale {
             Locale::En => Text::en(),
@@ -72,16 +137,69 @@ impl Site {
             }
         }
 
-        let templates = init_tera()?;
+        let theme = load_theme(config.theme_path.as_deref(), config.dev)?;
+        let templates = init_tera(&theme)?;
+
+        let menu = match &config.menu {
+            Some(menu) => menu.clone(),
+            None => vec![
+                MenuItem {
+                    title: text.home.to_owned(),
+                    url: "/".to_owned(),
+                },
+                MenuItem {
+                    title: text.archive.to_owned(),
+                    url: "/archive".to_owned(),
+                },
+            ],
+        };
 
-        Ok(Self {
+        let site = Self {
             article_manager: am,
+            media_manager: mm,
+            session_manager: sm,
+            user_manager: um,
+            ban_manager: bm,
+            token_manager: tm,
             text,
             db,
             metadata: Mutex::new(metadata),
-            templates,
-            admin: (config.username.clone(), config.password.clone()),
-        })
+            theme: RwLock::new(theme),
+            templates: RwLock::new(templates),
+            theme_path: config.theme_path.clone(),
+            reload: config.dev.then(|| broadcast::channel(16).0),
+            base_url: config
+                .base_url
+                .as_deref()
+                .unwrap_or_default()
+                .trim_end_matches('/')
+                .to_owned(),
+            robots_txt: config.robots_txt.clone(),
+            menu,
+            basic_auth: config.basic_auth.unwrap_or(false),
+            static_export: false,
+            trusted_proxies: config.trusted_proxies.clone().unwrap_or_default(),
+        };
+
+        two_factor::adopt_legacy(&site).await?;
+        site.sync_root_key().await?;
+
+        Ok(site)
+    }
+
+    async fn sync_root_key(&self) -> Result<()> {
+        let root = self.user_manager.root_username();
+        let previous = self.get_metadata_value(Self::ROOT_KEY).await?;
+
+        if previous.as_deref() == Some(root) {
+            return Ok(());
+        }
+
+        if let Some(previous) = previous {
+            self.user_manager.retire_root(&previous).await?;
+        }
+
+        self.set_metadata_value(Self::ROOT_KEY, Some(root)).await
     }
 
     async fn refresh_metadata(&self) -> Result<()> {
@@ -103,18 +221,170 @@ impl Site {
         Ok(())
     }
 
+    // Every stored key, including ones this version does not know about, but not secrets or
+    // what only makes sense for this site
+    pub async fn get_all_metadata(&self) -> Result<Vec<Metadata>> {
+        let metadata = MetadataEntity::find()
+            .filter(metadata::Column::Key.is_not_in(two_factor::PRIVATE_KEYS.iter().copied()))
+            .filter(metadata::Column::Key.ne(Self::ROOT_KEY))
+            .order_by_asc(metadata::Column::Key)
+            .all(&self.db)
+            .await?;
+
+        Ok(metadata)
+    }
+
+    // Replaces the given keys, others are left alone. Secrets are never taken from an import.
+    pub async fn set_metadata(&self, entries: Vec<Metadata>) -> Result<()> {
+        let txn = self.db.begin().await?;
+
+        for entry in entries {
+            if two_factor::PRIVATE_KEYS.contains(&entry.key.as_str()) || entry.key == Self::ROOT_KEY
+            {
+                warn!("Ignored metadata key {}", entry.key);
+                continue;
+            }
+
+            MetadataEntity::delete_by_id(entry.key.clone())
+                .exec(&txn)
+                .await?;
+
+            let entry = metadata::ActiveModel {
+                key: Set(entry.key),
+                val_int: Set(entry.val_int),
+                val_float: Set(entry.val_float),
+                val_string: Set(entry.val_string),
+            };
+
+            // The key is not an auto increment column, so there is no id to read back
+            MetadataEntity::insert(entry)
+                .exec_without_returning(&txn)
+                .await?;
+        }
+
+        txn.commit().await?;
+
+        self.refresh_metadata().await
+    }
+
+    // A value straight from the database, for keys that are not kept in memory
+    pub async fn get_metadata_value(&self, key: &str) -> Result<Option<String>> {
+        let value = get_value_from_db(&self.db, key).await?;
+
+        Ok(value.map(|value| value.to_string()))
+    }
+
+    // None removes the key
+    pub async fn set_metadata_value(&self, key: &str, value: Option<&str>) -> Result<()> {
+        let txn = self.db.begin().await?;
+
+        MetadataEntity::delete_by_id(key).exec(&txn).await?;
+
+        if let Some(value) = value {
+            let entry = metadata::ActiveModel {
+                key: Set(key.to_owned()),
+                val_int: Set(None),
+                val_float: Set(None),
+                val_string: Set(Some(value.to_owned())),
+            };
+
+            MetadataEntity::insert(entry)
+                .exec_without_returning(&txn)
+                .await?;
+        }
+
+        txn.commit().await?;
+
+        Ok(())
+    }
+
     pub fn render(&self, template: &'static str, context: &Context) -> Result<String> {
-        Ok(self.templates.render(template, context)?)
+        Ok(self.templates.read().render(template, context)?)
+    }
+
+    pub fn get_asset(&self, path: &str) -> Option<(Vec<u8>, &'static str)> {
+        let theme = self.theme.read();
+        let (data, mime_type) = theme.get_asset(path)?;
+
+        Some((data.to_vec(), mime_type))
+    }
+
+    pub fn get_asset_paths(&self) -> Vec<String> {
+        let theme = self.theme.read();
+        theme.asset_paths().into_iter().map(str::to_owned).collect()
+    }
+
+    pub fn is_dev(&self) -> bool {
+        self.reload.is_some()
+    }
+
+    // Directories to watch in development mode
+    pub fn get_theme_paths(&self) -> Vec<PathBuf> {
+        Theme::dev_paths(self.theme_path.as_deref())
+    }
+
+    // Reads templates and assets from disk again, a template that fails to parse leaves the
+    // old ones in place
+    pub fn reload_theme(&self) -> Result<()> {
+        let theme = load_theme(self.theme_path.as_deref(), self.is_dev())?;
+        let templates = init_tera(&theme)?;
+
+        *self.theme.write() = theme;
+        *self.templates.write() = templates;
+
+        if let Some(reload) = &self.reload {
+            // Nobody listening is fine
+            let _ = reload.send(());
+        }
+
+        Ok(())
+    }
+
+    pub fn subscribe_reload(&self) -> Option<broadcast::Receiver<()>> {
+        self.reload.as_ref().map(broadcast::Sender::subscribe)
     }
 
     pub fn get_author(&self) -> Str {
         self.get_metadata_string("author", self.text.author_default)
     }
 
+    // Whoever wrote the article, the site's author for articles from before there were users
+    pub async fn get_article_author(&self, article: &Article) -> Result<Str> {
+        let user = match article.author_id {
+            Some(author_id) => self.user_manager.get_user(author_id).await?,
+            None => None,
+        };
+
+        Ok(match user {
+            Some(user) => Str::Owned(user.display_name),
+            None => self.get_author(),
+        })
+    }
+
     pub fn get_site_name(&self) -> Str {
         self.get_metadata_string("site_name", self.text.site_name_default)
     }
 
+    pub fn get_trusted_proxies(&self) -> &[IpAddr] {
+        &self.trusted_proxies
+    }
+
+    // Absolute url of the site without the trailing slash, empty if not configured
+    pub fn get_base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    pub fn get_robots_txt(&self) -> String {
+        const DEFAULT: &str = "User-agent: *\nDisallow: /editor\nDisallow: /api/\n";
+
+        match &self.robots_txt {
+            Some(robots_txt) => robots_txt.to_owned(),
+            // The sitemap has to be given with an absolute url
+            None if self.base_url.is_empty() => DEFAULT.to_owned(),
+            None => format!("{DEFAULT}\nSitemap: {}/sitemap.xml\n", self.base_url),
+        }
+    }
+
     pub fn format_time(&self, timestamp: i64) -> String {
         (self.text.format_time)(timestamp)
     }
@@ -133,7 +403,7 @@ impl Site {
         };
 
         let value = match lock.get(key) {
-            Some(Type::String(s)) => Str::Owned(s.to_owned()),
+            Some(value) => Str::Owned(value.to_string()),
             None => Str::Static(default),
         };
 
@@ -149,6 +419,10 @@ impl Site {
         let site_name = self.get_site_name();
         context.insert("site_name", &site_name);
 
+        context.insert("menu", &self.menu);
+        context.insert("dev", &self.is_dev());
+        context.insert("static_export", &self.static_export);
+
         context
     }
 }
@@ -157,8 +431,7 @@ async fn get_value_from_db(db: &DatabaseConnection, key: &str) -> Result<Option<
     let entry: Option<Metadata> = MetadataEntity::find()
         .filter(metadata::Column::Key.eq(key))
         .one(db)
-        .await?
-        .map(|e| e.into());
+        .await?;
 
     let entry = match entry {
         Some(e) => e,
@@ -166,8 +439,8 @@ async fn get_value_from_db(db: &DatabaseConnection, key: &str) -> Result<Option<
     };
 
     let entry = match (entry.val_int, entry.val_float, entry.val_string) {
-        (Some(_), _, _) => todo!(),
-        (_, Some(_), _) => todo!(),
+        (Some(i), _, _) => Type::Int(i),
+        (_, Some(f), _) => Type::Float(f),
         (_, _, Some(s)) => Type::String(s),
         _ => return Err(anyhow::anyhow!("no value found for key {key}")),
     };
@@ -175,24 +448,22 @@ async fn get_value_from_db(db: &DatabaseConnection, key: &str) -> Result<Option<
     Ok(Some(entry))
 }
 
-fn init_tera() -> Result<Tera> {
-    let templates = {
-        mod template {
-            include!(concat!(env!("OUT_DIR"), "/template.rs"));
-        }
+fn load_theme(path: Option<&str>, dev: bool) -> Result<Theme> {
+    match (path, dev) {
+        (path, true) => Theme::load_dev(path),
+        (Some(path), false) => Theme::load(path),
+        (None, false) => Ok(Theme::default()),
+    }
+}
 
-        template::TEMPLATES
-            .into_iter()
-            .map(|(name, template)| try {
-                let template = std::str::from_utf8(template)?;
-                (name, template)
-            })
-            .collect::<Result<Vec<_>>>()?
-    };
+fn init_tera(theme: &Theme) -> Result<Tera> {
+    let templates = theme.templates()?;
 
     let mut tera = Tera::default();
     tera.add_raw_templates(templates)?;
-    tera.autoescape_on(vec![]);
+    // Titles, tags and names come from authors who are not trusted with the page. The
+    // feeds and the sitemap escape for XML themselves.
+    tera.autoescape_on(vec![".html"]);
 
     debug!("Loaded {} template(s)", tera.templates.len());
 
@@ -3,6 +3,8 @@ use chrono::{Local, LocalResult, TimeZone};
 pub struct Text {
     pub site_name_default: &'static str,
     pub author_default: &'static str,
+    pub home: &'static str,
+    pub archive: &'static str,
     pub format_time: fn(i64) -> String, // in: timestamp, out: formatted time
 }
 
@@ -20,6 +22,8 @@ impl Text {
         Self {
             site_name_default: "My Blog",
             author_default: "Author",
+            home: "Home",
+            archive: "Archive",
             format_time,
         }
     }
@@ -37,6 +41,8 @@ impl Text {
         Self {
             site_name_default: "我的博客",
             author_default: "作者",
+            home: "首页",
+            archive: "归档",
             format_time,
         }
     }
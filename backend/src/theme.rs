@@ -0,0 +1,178 @@
+use std::{
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use anyhow::{anyhow, Result};
+use hashbrown::HashMap;
+use log::{debug, info};
+
+use crate::util::*;
+
+mod template {
+    include!(concat!(env!("OUT_DIR"), "/template.rs"));
+}
+
+mod asset {
+    include!(concat!(env!("OUT_DIR"), "/asset.rs"));
+}
+
+// Where the built-in `template/` and `asset/` are in the source tree
+const SOURCE_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/..");
+
+// Templates and assets from a theme directory, laid out like the repository with
+// `template/` and `asset/`. Anything with the same name as a built-in one replaces it.
+// The default theme has nothing of its own and serves the built-ins
+#[derive(Default)]
+pub struct Theme {
+    templates: HashMap<String, String>,
+    assets: HashMap<String, (Vec<u8>, &'static str)>,
+}
+
+impl Theme {
+    pub fn load(path: &str) -> Result<Self> {
+        let path = Path::new(path);
+
+        let mut templates = HashMap::new();
+        for (name, data) in read_files(&path.join("template"))? {
+            let template = String::from_utf8(data)
+                .map_err(|_| anyhow!("theme template {name} is not valid UTF-8"))?;
+            templates.insert(name, template);
+        }
+
+        let mut assets = HashMap::new();
+        for (name, data) in read_files(&path.join("asset"))? {
+            let mime_type = mime_type_of(&name).unwrap_or("application/octet-stream");
+            assets.insert(name, (data, mime_type));
+        }
+
+        info!(
+            "Loaded {} template(s) and {} asset(s) from {}",
+            templates.len(),
+            assets.len(),
+            path.display()
+        );
+
+        Ok(Self { templates, assets })
+    }
+
+    // Development mode reads the built-ins from the source tree too, so editing them does not
+    // need a rebuild. Anything missing there, like the compiled frontend, still comes embedded
+    pub fn load_dev(path: Option<&str>) -> Result<Self> {
+        let mut theme = Self::load(SOURCE_PATH)?;
+
+        if let Some(path) = path {
+            let overlay = Self::load(path)?;
+            theme.templates.extend(overlay.templates);
+            theme.assets.extend(overlay.assets);
+        }
+
+        Ok(theme)
+    }
+
+    // Directories `load_dev` reads from
+    pub fn dev_paths(path: Option<&str>) -> Vec<PathBuf> {
+        let mut roots = vec![Path::new(SOURCE_PATH)];
+        roots.extend(path.map(Path::new));
+
+        roots
+            .into_iter()
+            .flat_map(|root| [root.join("template"), root.join("asset")])
+            .collect()
+    }
+
+    // Built-in templates with the theme ones laid over them
+    pub fn templates(&self) -> Result<Vec<(String, String)>> {
+        let mut templates = HashMap::new();
+
+        for (name, template) in template::TEMPLATES {
+            templates.insert(name.to_string(), std::str::from_utf8(template)?.to_owned());
+        }
+
+        for (name, template) in &self.templates {
+            debug!("Template {name} comes from the theme");
+            templates.insert(name.clone(), template.clone());
+        }
+
+        Ok(templates.into_iter().collect())
+    }
+
+    pub fn get_asset(&self, path: &str) -> Option<(&[u8], &'static str)> {
+        if let Some((data, mime_type)) = self.assets.get(path) {
+            return Some((data, mime_type));
+        }
+
+        asset::ASSETS
+            .iter()
+            .find(|(name, _, _)| *name == path)
+            .map(|(_, data, mime_type)| (*data, *mime_type))
+    }
+
+    // Every asset path, built-in or from the theme
+    pub fn asset_paths(&self) -> Vec<&str> {
+        let mut paths: Vec<_> = asset::ASSETS.iter().map(|(name, _, _)| *name).collect();
+        paths.extend(self.assets.keys().map(String::as_str));
+
+        paths.sort();
+        paths.dedup();
+
+        paths
+    }
+}
+
+// Every file under the given directories with its modification time, comparing two of these
+// tells whether anything was added, removed or changed
+pub fn snapshot(dirs: &[PathBuf]) -> Result<Vec<(PathBuf, SystemTime)>> {
+    let mut files = vec![];
+
+    for dir in dirs {
+        for path in walk(dir)? {
+            let modified = std::fs::metadata(&path)?.modified()?;
+            files.push((path, modified));
+        }
+    }
+
+    files.sort();
+
+    Ok(files)
+}
+
+// Files under `dir` keyed by their path relative to it, a missing directory is just empty
+fn read_files(dir: &Path) -> Result<Vec<(String, Vec<u8>)>> {
+    let mut files = vec![];
+
+    for path in walk(dir)? {
+        let name = path
+            .strip_prefix(dir)?
+            .to_str()
+            .ok_or(anyhow!("theme file {} is not valid UTF-8", path.display()))?
+            .replace('\\', "/");
+
+        files.push((name, std::fs::read(&path)?));
+    }
+
+    Ok(files)
+}
+
+fn walk(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = vec![];
+
+    if !dir.is_dir() {
+        return Ok(files);
+    }
+
+    let mut dirs = vec![dir.to_path_buf()];
+
+    while let Some(current) = dirs.pop() {
+        for entry in std::fs::read_dir(&current)? {
+            let path = entry?.path();
+
+            match path.is_dir() {
+                true => dirs.push(path),
+                false => files.push(path),
+            }
+        }
+    }
+
+    Ok(files)
+}
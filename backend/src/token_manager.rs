@@ -0,0 +1,178 @@
+use anyhow::{anyhow, Result};
+use axum::http::Method;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use data_encoding::HEXLOWER;
+use log::info;
+use rand::{rngs::OsRng, RngCore};
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, QueryOrder, Set,
+};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use entity::{
+    api_token::{self, Model as ApiToken},
+    prelude::ApiToken as ApiTokenEntity,
+};
+
+use crate::util::*;
+
+// Makes tokens easy to spot in logs and leaked files
+const TOKEN_PREFIX: &str = "blog_";
+
+// What a token may do, stored comma separated in the `scopes` column
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Scope {
+    // Fetching articles, their revisions and the media list
+    Read,
+    // Creating, updating and deleting articles, and restoring revisions
+    Articles,
+    // Uploading media
+    Media,
+}
+
+impl Scope {
+    pub const ALL: [Scope; 3] = [Scope::Read, Scope::Articles, Scope::Media];
+
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|scope| scope.name() == name)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Read => "read",
+            Self::Articles => "articles",
+            Self::Media => "media",
+        }
+    }
+
+    // The scope a request needs, `None` for anything tokens cannot do, like the editor pages
+    pub fn required(method: &Method, path: &str) -> Option<Self> {
+        if !path.starts_with("/api/") {
+            return None;
+        }
+
+        match *method {
+            Method::GET | Method::HEAD => Some(Self::Read),
+            _ if path.starts_with("/api/article") => Some(Self::Articles),
+            _ if path.starts_with("/api/media") => Some(Self::Media),
+            _ => None,
+        }
+    }
+
+    pub fn of(token: &ApiToken) -> Vec<Self> {
+        token.scopes.split(',').filter_map(Self::parse).collect()
+    }
+}
+
+pub struct TokenManager {
+    db: DatabaseConnection,
+}
+
+impl TokenManager {
+    pub fn new(db: DatabaseConnection) -> Self {
+        Self { db }
+    }
+
+    // The token itself is only returned here, just its hash is kept
+    pub async fn create_token(
+        &self,
+        user_id: i64,
+        name: &str,
+        scopes: &[Scope],
+    ) -> Result<(ApiToken, String)> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(anyhow!("token name is required"));
+        }
+
+        if scopes.is_empty() {
+            return Err(anyhow!("a token needs at least one scope"));
+        }
+
+        let mut bytes = [0; 32];
+        OsRng.fill_bytes(&mut bytes);
+        let secret = format!("{TOKEN_PREFIX}{}", URL_SAFE_NO_PAD.encode(bytes));
+
+        let scopes: Vec<_> = Scope::ALL
+            .into_iter()
+            .filter(|scope| scopes.contains(scope))
+            .map(|scope| scope.name())
+            .collect();
+
+        let token = api_token::ActiveModel {
+            user_id: Set(user_id),
+            name: Set(name.to_owned()),
+            token_hash: Set(hash_token(&secret)),
+            scopes: Set(scopes.join(",")),
+            created: Set(timestamp()),
+            last_used: Set(None),
+            ..Default::default()
+        };
+
+        let token = token.insert(&self.db).await?;
+
+        info!("Created API token {} for user {user_id}", token.name);
+
+        Ok((token, secret))
+    }
+
+    // The token a bearer presented, marked as used. The secret has 256 bits, so looking it up by
+    // hash gives nothing away that comparing in constant time would protect.
+    pub async fn use_token(&self, secret: &str) -> Result<Option<ApiToken>> {
+        let token = ApiTokenEntity::find()
+            .filter(api_token::Column::TokenHash.eq(hash_token(secret)))
+            .one(&self.db)
+            .await?;
+
+        let token = match token {
+            Some(token) => token,
+            None => return Ok(None),
+        };
+
+        let mut active: api_token::ActiveModel = token.into();
+        active.last_used = Set(Some(timestamp()));
+        let token = active.update(&self.db).await?;
+
+        Ok(Some(token))
+    }
+
+    pub async fn get_token(&self, token_id: i64) -> Result<Option<ApiToken>> {
+        let token = ApiTokenEntity::find_by_id(token_id).one(&self.db).await?;
+
+        Ok(token)
+    }
+
+    // Every user's tokens when `user_id` is `None`, newest first
+    pub async fn get_tokens(&self, user_id: Option<i64>) -> Result<Vec<ApiToken>> {
+        let mut query = ApiTokenEntity::find();
+
+        if let Some(user_id) = user_id {
+            query = query.filter(api_token::Column::UserId.eq(user_id));
+        }
+
+        let tokens = query
+            .order_by_desc(api_token::Column::TokenId)
+            .all(&self.db)
+            .await?;
+
+        Ok(tokens)
+    }
+
+    pub async fn revoke_token(&self, token_id: i64) -> Result<bool> {
+        let result = ApiTokenEntity::delete_by_id(token_id)
+            .exec(&self.db)
+            .await?;
+
+        if result.rows_affected > 0 {
+            info!("Revoked API token {token_id}");
+        }
+
+        Ok(result.rows_affected > 0)
+    }
+}
+
+fn hash_token(secret: &str) -> String {
+    HEXLOWER.encode(&Sha256::digest(secret.as_bytes()))
+}
@@ -0,0 +1,148 @@
+use anyhow::{anyhow, Result};
+use data_encoding::BASE32_NOPAD;
+use hmac::{Hmac, Mac};
+use rand::{rngs::OsRng, RngCore};
+use sha1::Sha1;
+
+use crate::{password::constant_time_eq, util::*};
+
+// RFC 6238 with the parameters every authenticator app assumes: SHA-1, 30 seconds, 6 digits
+const STEP: i64 = 30;
+const DIGITS: usize = 6;
+// Codes from one step before or after are taken as well, for clocks that are a little off
+const SKEW: i64 = 1;
+
+// 160 bits as RFC 4226 recommends, in the base32 the apps expect
+pub fn generate_secret() -> String {
+    let mut secret = [0; 20];
+    OsRng.fill_bytes(&mut secret);
+    BASE32_NOPAD.encode(&secret)
+}
+
+pub fn decode_secret(secret: &str) -> Result<Vec<u8>> {
+    BASE32_NOPAD
+        .decode(secret.trim_end_matches('=').as_bytes())
+        .map_err(|e| anyhow!("invalid TOTP secret: {e}"))
+}
+
+// The step the code belongs to if it is valid at `time`, a Unix timestamp. Callers remember
+// the step and pass it back as `last_step`, so a code cannot be used twice.
+pub fn verify_at(secret: &[u8], code: &str, time: i64, last_step: Option<i64>) -> Option<i64> {
+    let code = code.trim();
+
+    if code.len() != DIGITS || !code.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+
+    let step = time.div_euclid(STEP);
+
+    (step - SKEW..=step + SKEW)
+        .filter(|step| *step >= 0 && Some(*step) > last_step)
+        .find(|step| {
+            let expected = hotp(secret, *step as u64, DIGITS as u32);
+            constant_time_eq(expected.as_bytes(), code.as_bytes())
+        })
+}
+
+// What the QR code holds, see https://github.com/google/google-authenticator/wiki/Key-Uri-Format
+pub fn otpauth_uri(issuer: &str, account: &str, secret: &str) -> String {
+    format!(
+        "otpauth://totp/{}:{}?secret={secret}&issuer={}&algorithm=SHA1&digits={DIGITS}&period={STEP}",
+        urlencode(issuer),
+        urlencode(account),
+        urlencode(issuer)
+    )
+}
+
+// RFC 4226, dynamic truncation of an HMAC over the counter
+fn hotp(secret: &[u8], counter: u64, digits: u32) -> String {
+    // HMAC takes keys of any length
+    let mut mac = Hmac::<Sha1>::new_from_slice(secret).unwrap();
+    mac.update(&counter.to_be_bytes());
+    let hash = mac.finalize().into_bytes();
+
+    let offset = (hash[hash.len() - 1] & 0xf) as usize;
+    let binary = u32::from_be_bytes([
+        hash[offset] & 0x7f,
+        hash[offset + 1],
+        hash[offset + 2],
+        hash[offset + 3],
+    ]);
+
+    format!(
+        "{:0width$}",
+        binary % 10u32.pow(digits),
+        width = digits as usize
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: &[u8] = b"12345678901234567890";
+
+    // RFC 4226 Appendix D
+    #[test]
+    fn hotp_values() {
+        let expected = [
+            "755224", "287082", "359152", "969429", "338314", "254676", "287922", "162583",
+            "399871", "520489",
+        ];
+
+        for (counter, code) in expected.iter().enumerate() {
+            assert_eq!(hotp(SECRET, counter as u64, 6), *code);
+        }
+    }
+
+    // RFC 6238 Appendix B, the SHA-1 ones
+    #[test]
+    fn totp_vectors() {
+        let expected = [
+            (59, "94287082"),
+            (1111111109, "07081804"),
+            (1111111111, "14050471"),
+            (1234567890, "89005924"),
+            (2000000000, "69279037"),
+            (20000000000, "65353130"),
+        ];
+
+        for (time, code) in expected {
+            assert_eq!(hotp(SECRET, (time / STEP) as u64, 8), code);
+
+            // Apps show the last six digits
+            let step = verify_at(SECRET, &code[2..], time, None);
+            assert_eq!(step, Some(time / STEP));
+        }
+    }
+
+    #[test]
+    fn window() {
+        // Belongs to step 1, 30 to 59
+        let code = hotp(SECRET, 1, 6);
+
+        assert_eq!(verify_at(SECRET, &code, 0, None), Some(1));
+        assert_eq!(verify_at(SECRET, &code, 45, None), Some(1));
+        assert_eq!(verify_at(SECRET, &code, 89, None), Some(1));
+        assert_eq!(verify_at(SECRET, &code, 90, None), None);
+        assert_eq!(verify_at(SECRET, &code, 1234567890, None), None);
+    }
+
+    #[test]
+    fn replay() {
+        let code = hotp(SECRET, 1, 6);
+
+        assert_eq!(verify_at(SECRET, &code, 45, Some(0)), Some(1));
+        assert_eq!(verify_at(SECRET, &code, 45, Some(1)), None);
+        assert_eq!(verify_at(SECRET, &code, 75, Some(2)), None);
+    }
+
+    #[test]
+    fn malformed_codes() {
+        let code = hotp(SECRET, 1, 6);
+
+        assert_eq!(verify_at(SECRET, &format!(" {code} "), 45, None), Some(1));
+        assert_eq!(verify_at(SECRET, &code[1..], 45, None), None);
+        assert_eq!(verify_at(SECRET, "12345a", 45, None), None);
+    }
+}
@@ -0,0 +1,198 @@
+use anyhow::Result;
+use data_encoding::{BASE32_NOPAD, HEXLOWER};
+use log::info;
+use rand::{rngs::OsRng, RngCore};
+use sha2::{Digest, Sha256};
+
+use entity::user::Model as User;
+
+use crate::{
+    password::constant_time_eq,
+    site::Site,
+    totp,
+    user_manager::{TwoFactorFields, UserManager},
+};
+
+// Where two-factor was kept when only the account from the config could have it, all of them
+// as strings in the metadata table. Moved to that account by `adopt_legacy`.
+const SECRET_KEY: &str = "totp_secret";
+const PENDING_SECRET_KEY: &str = "totp_pending_secret";
+const RECOVERY_CODES_KEY: &str = "totp_recovery_codes";
+const LAST_STEP_KEY: &str = "totp_last_step";
+
+// Left out of exports and never taken from an import
+pub const PRIVATE_KEYS: &[&str] = &[
+    SECRET_KEY,
+    PENDING_SECRET_KEY,
+    RECOVERY_CODES_KEY,
+    LAST_STEP_KEY,
+];
+
+const RECOVERY_CODE_COUNT: usize = 10;
+
+pub fn is_enabled(user: &User) -> bool {
+    user.totp_secret.is_some()
+}
+
+// The secret to scan, the same one until a code from it is entered so reloading the page does
+// not invalidate what the app already has
+pub async fn begin_enrollment(users: &UserManager, user: &User) -> Result<String> {
+    if let Some(pending) = &user.totp_pending_secret {
+        return Ok(pending.clone());
+    }
+
+    let secret = totp::generate_secret();
+
+    let fields = TwoFactorFields {
+        pending_secret: Some(Some(secret.clone())),
+        ..Default::default()
+    };
+    users.update_two_factor(user.user_id, fields).await?;
+
+    Ok(secret)
+}
+
+// Turns two-factor on if the code matches the pending secret, and hands out the recovery
+// codes. They are only stored hashed, so this is the one chance to see them.
+pub async fn finish_enrollment(
+    users: &UserManager,
+    user: &User,
+    code: &str,
+    time: i64,
+) -> Result<Option<Vec<String>>> {
+    let pending = match &user.totp_pending_secret {
+        Some(pending) => pending,
+        None => return Ok(None),
+    };
+
+    let step = match totp::verify_at(&totp::decode_secret(pending)?, code, time, None) {
+        Some(step) => step,
+        None => return Ok(None),
+    };
+
+    let codes: Vec<_> = (0..RECOVERY_CODE_COUNT)
+        .map(|_| generate_recovery_code())
+        .collect();
+    let hashes: Vec<_> = codes.iter().map(|code| hash_recovery_code(code)).collect();
+
+    let fields = TwoFactorFields {
+        secret: Some(Some(pending.clone())),
+        pending_secret: Some(None),
+        recovery_codes: Some(Some(hashes.join("\n"))),
+        last_step: Some(Some(step)),
+    };
+    users.update_two_factor(user.user_id, fields).await?;
+
+    Ok(Some(codes))
+}
+
+pub async fn disable(users: &UserManager, user: &User) -> Result<()> {
+    let fields = TwoFactorFields {
+        secret: Some(None),
+        pending_secret: Some(None),
+        recovery_codes: Some(None),
+        last_step: Some(None),
+    };
+    users.update_two_factor(user.user_id, fields).await?;
+
+    Ok(())
+}
+
+// A code from the authenticator, or one of the recovery codes, which then stops working.
+// Always true when two-factor is off.
+pub async fn verify(users: &UserManager, user: &User, code: &str, time: i64) -> Result<bool> {
+    let secret = match &user.totp_secret {
+        Some(secret) => totp::decode_secret(secret)?,
+        None => return Ok(true),
+    };
+
+    // Someone watching over a shoulder could otherwise use the same code again
+    if let Some(step) = totp::verify_at(&secret, code, time, user.totp_last_step) {
+        let fields = TwoFactorFields {
+            last_step: Some(Some(step)),
+            ..Default::default()
+        };
+        users.update_two_factor(user.user_id, fields).await?;
+
+        return Ok(true);
+    }
+
+    verify_recovery_code(users, user, code).await
+}
+
+async fn verify_recovery_code(users: &UserManager, user: &User, code: &str) -> Result<bool> {
+    let hashes = user.totp_recovery_codes.as_deref().unwrap_or_default();
+
+    let hash = hash_recovery_code(code);
+    let mut remaining: Vec<_> = hashes.lines().collect();
+
+    let position = match remaining
+        .iter()
+        .position(|stored| constant_time_eq(stored.as_bytes(), hash.as_bytes()))
+    {
+        Some(position) => position,
+        None => return Ok(false),
+    };
+
+    remaining.remove(position);
+
+    let fields = TwoFactorFields {
+        recovery_codes: Some(Some(remaining.join("\n"))),
+        ..Default::default()
+    };
+    users.update_two_factor(user.user_id, fields).await?;
+
+    Ok(true)
+}
+
+// Two-factor set up before it was per user belongs to the account from the config
+pub async fn adopt_legacy(site: &Site) -> Result<()> {
+    let users = &site.user_manager;
+
+    if let Some(secret) = site.get_metadata_value(SECRET_KEY).await? {
+        let root = users.get_user_by_name(users.root_username()).await?;
+
+        if let Some(root) = root.filter(|root| !is_enabled(root)) {
+            let last_step = site
+                .get_metadata_value(LAST_STEP_KEY)
+                .await?
+                .and_then(|step| step.parse().ok());
+
+            let fields = TwoFactorFields {
+                secret: Some(Some(secret)),
+                pending_secret: Some(None),
+                recovery_codes: Some(site.get_metadata_value(RECOVERY_CODES_KEY).await?),
+                last_step: Some(last_step),
+            };
+            users.update_two_factor(root.user_id, fields).await?;
+
+            info!("Moved two-factor authentication to user {}", root.username);
+        }
+    }
+
+    for key in PRIVATE_KEYS {
+        site.set_metadata_value(key, None).await?;
+    }
+
+    Ok(())
+}
+
+// Like `abcde-fghij`, 50 bits is plenty for something that only works once
+fn generate_recovery_code() -> String {
+    let mut bytes = [0; 10];
+    OsRng.fill_bytes(&mut bytes);
+
+    let code = BASE32_NOPAD.encode(&bytes).to_lowercase();
+    format!("{}-{}", &code[..5], &code[5..10])
+}
+
+// Case, spaces and dashes do not matter when typing a code in
+fn hash_recovery_code(code: &str) -> String {
+    let code: String = code
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect();
+
+    HEXLOWER.encode(&Sha256::digest(code.as_bytes()))
+}
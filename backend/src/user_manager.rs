@@ -0,0 +1,433 @@
+use anyhow::{anyhow, Result};
+use log::{info, warn};
+use once_cell::sync::Lazy;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, QueryOrder, Set,
+};
+use serde::Serialize;
+
+use entity::{
+    api_token,
+    article::Model as Article,
+    prelude::{ApiToken as ApiTokenEntity, Session as SessionEntity, User as UserEntity},
+    session,
+    user::{self, Model as User},
+};
+
+use crate::{
+    password::{hash_password, is_hashed, verify_password},
+    util::*,
+};
+
+// Checked against when the username is unknown, so timing does not tell which part was wrong
+static DUMMY_HASH: Lazy<String> = Lazy::new(|| hash_password("").unwrap_or_default());
+
+const MAX_USERNAME_LENGTH: usize = 64;
+const MIN_PASSWORD_LENGTH: usize = 8;
+
+// Stored in the `role` column of the user table
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    // Everything, including managing users
+    Admin,
+    // Every article, but not the users
+    Editor,
+    // Only their own articles
+    Author,
+}
+
+impl Role {
+    pub const ALL: [Role; 3] = [Role::Admin, Role::Editor, Role::Author];
+
+    pub fn of(user: &User) -> Self {
+        match user.role {
+            0 => Self::Admin,
+            1 => Self::Editor,
+            2 => Self::Author,
+            value => {
+                warn!("Unknown user role {value}, treating as author");
+                Self::Author
+            }
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|role| role.name() == name)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Admin => "admin",
+            Self::Editor => "editor",
+            Self::Author => "author",
+        }
+    }
+
+    pub fn can_edit_all(self) -> bool {
+        matches!(self, Self::Admin | Self::Editor)
+    }
+
+    fn column(self) -> i32 {
+        match self {
+            Self::Admin => 0,
+            Self::Editor => 1,
+            Self::Author => 2,
+        }
+    }
+}
+
+// Admins and editors can change every article, authors only the ones they created
+pub fn can_edit(user: &User, article: &Article) -> bool {
+    Role::of(user).can_edit_all() || article.author_id == Some(user.user_id)
+}
+
+// Fields accepted by the write path, `None` means "leave unchanged" on update
+#[derive(Default)]
+pub struct UserFields {
+    pub username: Option<String>,
+    pub display_name: Option<String>,
+    // In plain text, it is hashed before it is stored
+    pub password: Option<String>,
+    pub role: Option<Role>,
+}
+
+// Two-factor state as kept by `two_factor`, `None` leaves a column alone and `Some(None)`
+// clears it
+#[derive(Default)]
+pub struct TwoFactorFields {
+    pub secret: Option<Option<String>>,
+    pub pending_secret: Option<Option<String>>,
+    pub recovery_codes: Option<Option<String>>,
+    pub last_step: Option<Option<i64>>,
+}
+
+pub struct UserManager {
+    db: DatabaseConnection,
+    // The account from the config, always an admin and only changed through the config
+    root: String,
+}
+
+impl UserManager {
+    // Makes sure the account from the config exists with the password from the config
+    pub async fn new(db: DatabaseConnection, username: &str, password: &str) -> Result<Self> {
+        let manager = Self {
+            db,
+            root: username.to_owned(),
+        };
+
+        manager.sync_root(password).await?;
+
+        Ok(manager)
+    }
+
+    pub fn root_username(&self) -> &str {
+        &self.root
+    }
+
+    pub fn is_root(&self, user: &User) -> bool {
+        user.username == self.root
+    }
+
+    async fn sync_root(&self, password: &str) -> Result<()> {
+        let existing = self.get_user_by_name(&self.root).await?;
+
+        // A plain text password is hashed, but only when it changed, hashing is slow
+        let hash = match (&existing, is_hashed(password)) {
+            (_, true) => password.to_owned(),
+            (Some(existing), false) if verify_password(&existing.password, password) => {
+                existing.password.clone()
+            }
+            (_, false) => hash_password(password)?,
+        };
+
+        match existing {
+            Some(existing) => {
+                if existing.password == hash && Role::of(&existing) == Role::Admin {
+                    return Ok(());
+                }
+
+                let mut active: user::ActiveModel = existing.into();
+                active.password = Set(hash);
+                active.role = Set(Role::Admin.column());
+                active.update(&self.db).await?;
+            }
+            None => {
+                let user = user::ActiveModel {
+                    username: Set(self.root.clone()),
+                    display_name: Set(self.root.clone()),
+                    password: Set(hash),
+                    role: Set(Role::Admin.column()),
+                    created: Set(timestamp()),
+                    ..Default::default()
+                };
+                user.insert(&self.db).await?;
+
+                info!("Created user {} from the config", self.root);
+            }
+        }
+
+        Ok(())
+    }
+
+    // The user with these credentials, slow on purpose
+    pub async fn authenticate(&self, username: &str, password: &str) -> Result<Option<User>> {
+        let user = self.get_user_by_name(username).await?;
+
+        // Hash anyway when the name is wrong, so timing does not tell which one was
+        let stored = match &user {
+            Some(user) => user.password.clone(),
+            None => DUMMY_HASH.clone(),
+        };
+        let password = password.to_owned();
+
+        let matches = tokio::task::spawn_blocking(move || verify_password(&stored, &password))
+            .await
+            .unwrap_or(false);
+
+        Ok(user.filter(|_| matches))
+    }
+
+    pub async fn get_user(&self, user_id: i64) -> Result<Option<User>> {
+        let user = UserEntity::find_by_id(user_id).one(&self.db).await?;
+
+        Ok(user)
+    }
+
+    pub async fn get_user_by_name(&self, username: &str) -> Result<Option<User>> {
+        let user = UserEntity::find()
+            .filter(user::Column::Username.eq(username))
+            .one(&self.db)
+            .await?;
+
+        Ok(user)
+    }
+
+    // Oldest first, so the account from the config leads
+    pub async fn get_all_users(&self) -> Result<Vec<User>> {
+        let users = UserEntity::find()
+            .order_by_asc(user::Column::UserId)
+            .all(&self.db)
+            .await?;
+
+        Ok(users)
+    }
+
+    pub async fn create_user(&self, fields: UserFields) -> Result<User> {
+        let username = fields.username.unwrap_or_default();
+        check_username(&username)?;
+
+        if self.get_user_by_name(&username).await?.is_some() {
+            return Err(anyhow!("user {username} already exists"));
+        }
+
+        let password = fields.password.ok_or(anyhow!("password is required"))?;
+        let hash = hash_new_password(password).await?;
+
+        let display_name = match fields.display_name {
+            Some(display_name) if !display_name.trim().is_empty() => display_name.trim().to_owned(),
+            _ => username.clone(),
+        };
+
+        let user = user::ActiveModel {
+            username: Set(username),
+            display_name: Set(display_name),
+            password: Set(hash),
+            role: Set(fields.role.unwrap_or(Role::Author).column()),
+            created: Set(timestamp()),
+            ..Default::default()
+        };
+
+        let user = user.insert(&self.db).await?;
+
+        info!("Created user {}", user.username);
+
+        Ok(user)
+    }
+
+    pub async fn update_user(&self, user_id: i64, fields: UserFields) -> Result<Option<User>> {
+        let user = match self.get_user(user_id).await? {
+            Some(user) => user,
+            None => return Ok(None),
+        };
+
+        let is_root = self.is_root(&user);
+        let old_username = user.username.clone();
+        let old_role = Role::of(&user);
+        let mut active: user::ActiveModel = user.into();
+
+        if let Some(username) = fields.username {
+            if username != old_username {
+                if is_root {
+                    return Err(anyhow!(
+                        "the account from the config can only be renamed there"
+                    ));
+                }
+
+                check_username(&username)?;
+
+                if self.get_user_by_name(&username).await?.is_some() {
+                    return Err(anyhow!("user {username} already exists"));
+                }
+
+                active.username = Set(username);
+            }
+        }
+
+        if let Some(display_name) = fields.display_name {
+            if !display_name.trim().is_empty() {
+                active.display_name = Set(display_name.trim().to_owned());
+            }
+        }
+
+        let password_changed = fields.password.is_some();
+        if let Some(password) = fields.password {
+            if is_root {
+                return Err(anyhow!(
+                    "the password of the account from the config is set there"
+                ));
+            }
+
+            active.password = Set(hash_new_password(password).await?);
+        }
+
+        if let Some(role) = fields.role {
+            if is_root && role != Role::Admin {
+                return Err(anyhow!("the account from the config is always an admin"));
+            }
+
+            active.role = Set(role.column());
+        }
+
+        let user = active.update(&self.db).await?;
+
+        // Whoever was let in with the old password or rights is not anymore. Sessions are also
+        // tied to the name, so a renamed user logs in again.
+        if password_changed || Role::of(&user).column() > old_role.column() {
+            self.revoke_access(user.user_id, &old_username).await?;
+        } else if user.username != old_username {
+            self.delete_sessions(&old_username).await?;
+        }
+
+        info!("Updated user {}", user.username);
+
+        Ok(Some(user))
+    }
+
+    pub async fn update_two_factor(&self, user_id: i64, fields: TwoFactorFields) -> Result<User> {
+        let mut active = user::ActiveModel {
+            user_id: Set(user_id),
+            ..Default::default()
+        };
+
+        if let Some(secret) = fields.secret {
+            active.totp_secret = Set(secret);
+        }
+
+        if let Some(pending_secret) = fields.pending_secret {
+            active.totp_pending_secret = Set(pending_secret);
+        }
+
+        if let Some(recovery_codes) = fields.recovery_codes {
+            active.totp_recovery_codes = Set(recovery_codes);
+        }
+
+        if let Some(last_step) = fields.last_step {
+            active.totp_last_step = Set(last_step);
+        }
+
+        let user = active.update(&self.db).await?;
+
+        Ok(user)
+    }
+
+    // Their articles are left to the caller, see `ArticleManager::remove_author`
+    pub async fn delete_user(&self, user_id: i64) -> Result<bool> {
+        let user = match self.get_user(user_id).await? {
+            Some(user) => user,
+            None => return Ok(false),
+        };
+
+        if self.is_root(&user) {
+            return Err(anyhow!("the account from the config cannot be deleted"));
+        }
+
+        UserEntity::delete_by_id(user_id).exec(&self.db).await?;
+        self.revoke_access(user_id, &user.username).await?;
+
+        info!("Deleted user {}", user.username);
+
+        Ok(true)
+    }
+
+    // The account that came from the config before it was renamed there is an author from now
+    // on, rather than an admin nobody remembers
+    pub async fn retire_root(&self, username: &str) -> Result<()> {
+        let user = match self.get_user_by_name(username).await? {
+            Some(user) if Role::of(&user) == Role::Admin => user,
+            _ => return Ok(()),
+        };
+
+        let user_id = user.user_id;
+        let mut active: user::ActiveModel = user.into();
+        active.role = Set(Role::Author.column());
+        active.update(&self.db).await?;
+
+        self.revoke_access(user_id, username).await?;
+
+        warn!("User {username} is no longer the account from the config and was made an author");
+
+        Ok(())
+    }
+
+    // Logs the user out everywhere and revokes their API tokens
+    async fn revoke_access(&self, user_id: i64, username: &str) -> Result<()> {
+        self.delete_sessions(username).await?;
+
+        ApiTokenEntity::delete_many()
+            .filter(api_token::Column::UserId.eq(user_id))
+            .exec(&self.db)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn delete_sessions(&self, username: &str) -> Result<()> {
+        SessionEntity::delete_many()
+            .filter(session::Column::Username.eq(username))
+            .exec(&self.db)
+            .await?;
+
+        Ok(())
+    }
+}
+
+// Ends up in cookies, logs and the Basic header, so nothing fancy
+fn check_username(username: &str) -> Result<()> {
+    if username.is_empty() || username.len() > MAX_USERNAME_LENGTH {
+        return Err(anyhow!(
+            "username must be 1 to {MAX_USERNAME_LENGTH} characters"
+        ));
+    }
+
+    if !username
+        .chars()
+        .all(|c| c.is_alphanumeric() || matches!(c, '-' | '_' | '.'))
+    {
+        return Err(anyhow!(
+            "username can only contain letters, digits, '-', '_' and '.'"
+        ));
+    }
+
+    Ok(())
+}
+
+async fn hash_new_password(password: String) -> Result<String> {
+    if password.chars().count() < MIN_PASSWORD_LENGTH {
+        return Err(anyhow!(
+            "password must be at least {MIN_PASSWORD_LENGTH} characters"
+        ));
+    }
+
+    tokio::task::spawn_blocking(move || hash_password(&password)).await?
+}
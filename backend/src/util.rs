@@ -1,14 +1,15 @@
-use std::io::Cursor;
+use std::{fmt, io::Cursor};
 
 use anyhow::Result;
+use axum::http::StatusCode;
 use chrono::Utc;
+use hashbrown::HashMap;
 use minify_html::{minify, Cfg};
 use once_cell::sync::Lazy;
 use prost::Message;
 
 pub fn protobuf_encode<T: Message>(message: T) -> Result<Vec<u8>> {
-    let mut buffer = Vec::new();
-    buffer.reserve(message.encoded_len());
+    let mut buffer = Vec::with_capacity(message.encoded_len());
     message.encode(&mut buffer)?;
     Ok(buffer)
 }
@@ -21,12 +22,96 @@ where
     Ok(message)
 }
 
+// Something wrong with the request rather than with us, see `error_status`
+#[derive(Debug)]
+pub struct Invalid(pub String);
+
+impl fmt::Display for Invalid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for Invalid {}
+
+pub fn invalid(message: impl Into<String>) -> anyhow::Error {
+    Invalid(message.into()).into()
+}
+
+// 400 for a request that could not be decoded or was turned down, 500 for anything else
+pub fn error_status(e: &anyhow::Error) -> StatusCode {
+    match e.is::<Invalid>() || e.is::<prost::DecodeError>() {
+        true => StatusCode::BAD_REQUEST,
+        false => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
 pub fn minify_html(html: String) -> Result<String> {
-    static CFG: Lazy<Cfg> = Lazy::new(|| Cfg::spec_compliant());
+    static CFG: Lazy<Cfg> = Lazy::new(Cfg::spec_compliant);
     let html = html.as_bytes();
     Ok(std::str::from_utf8(&minify(html, &CFG))?.to_owned())
 }
 
+// Percent-encode everything but unreserved characters, suitable for a path segment
+pub fn urlencode(s: &str) -> String {
+    let mut encoded = String::with_capacity(s.len());
+
+    for byte in s.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{byte:02X}")),
+        }
+    }
+
+    encoded
+}
+
+// Reverses `urlencode`, malformed escapes are kept as they are
+pub fn urldecode(s: &str) -> Result<String> {
+    let bytes = s.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let byte = match (bytes[i], s.get(i + 1..i + 3)) {
+            (b'%', Some(hex)) if hex.bytes().all(|b| b.is_ascii_hexdigit()) => {
+                u8::from_str_radix(hex, 16).ok()
+            }
+            _ => None,
+        };
+
+        match byte {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+
+    Ok(String::from_utf8(decoded)?)
+}
+
+// Looks up the MIME type of a file name by its extension in `mime.types`
+pub fn mime_type_of(name: &str) -> Option<&'static str> {
+    static MIME_TYPES: Lazy<HashMap<&'static str, &'static str>> = Lazy::new(|| {
+        mod mime {
+            include!(concat!(env!("OUT_DIR"), "/mime.rs"));
+        }
+
+        mime::MIME_TYPES.iter().copied().collect()
+    });
+
+    let (_, ext) = name.rsplit_once('.')?;
+
+    MIME_TYPES.get(ext.to_lowercase().as_str()).copied()
+}
+
 #[inline]
 pub fn timestamp() -> i64 {
     Utc::now().timestamp()
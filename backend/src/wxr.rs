@@ -0,0 +1,153 @@
+use anyhow::{anyhow, Result};
+use chrono::{Local, NaiveDateTime, TimeZone};
+use roxmltree::{Document, Node};
+
+// The version at the end differs between WordPress releases
+const WP_NAMESPACE: &str = "http://wordpress.org/export/";
+const CONTENT_NAMESPACE: &str = "http://purl.org/rss/1.0/modules/content/";
+
+// WordPress writes this for dates that were never set, e.g. the GMT date of a draft
+const ZERO_DATE: &str = "0000-00-00 00:00:00";
+
+// One <item> of a WordPress eXtended RSS export
+pub struct Item {
+    pub id: String,
+    pub title: String,
+    // Percent-encoded when it has non-ASCII characters, empty for most drafts
+    pub slug: String,
+    // Old permalink and the `?p=<id>` style guid
+    pub link: String,
+    pub guid: String,
+    // post, page, attachment, nav_menu_item...
+    pub post_type: String,
+    // publish, future, draft, pending, private, trash...
+    pub status: String,
+    pub created: Option<i64>,
+    pub updated: Option<i64>,
+    // HTML, with paragraphs separated by blank lines rather than <p> in older exports
+    pub content: String,
+    pub excerpt: String,
+    // Categories and tags alike
+    pub tags: Vec<String>,
+}
+
+pub fn parse(xml: &str) -> Result<Vec<Item>> {
+    let document = Document::parse(xml)?;
+
+    let channel = document
+        .root_element()
+        .children()
+        .find(|node| node.has_tag_name("channel"))
+        .ok_or(anyhow!("no <channel> in the export, is this a WXR file?"))?;
+
+    let items = channel
+        .children()
+        .filter(|node| node.has_tag_name("item"))
+        .map(parse_item)
+        .collect();
+
+    Ok(items)
+}
+
+fn parse_item(item: Node) -> Item {
+    let created = parse_date(wp_text(item, "post_date_gmt"), wp_text(item, "post_date"));
+    let updated = parse_date(
+        wp_text(item, "post_modified_gmt"),
+        wp_text(item, "post_modified"),
+    );
+
+    let mut tags = vec![];
+    for category in item.children().filter(|node| node.has_tag_name("category")) {
+        // Every post without a category gets this one, it tells nothing
+        if category.attribute("nicename") == Some("uncategorized") {
+            continue;
+        }
+
+        if let Some(name) = category
+            .text()
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+        {
+            if !tags.iter().any(|tag| tag == name) {
+                tags.push(name.to_owned());
+            }
+        }
+    }
+
+    let excerpt = item
+        .children()
+        .find(|node| {
+            node.tag_name().name() == "encoded"
+                && node
+                    .tag_name()
+                    .namespace()
+                    .is_some_and(|ns| ns.contains("/excerpt/"))
+        })
+        .and_then(|node| node.text());
+
+    Item {
+        id: wp_text(item, "post_id").to_owned(),
+        title: text(item, None, "title").to_owned(),
+        slug: wp_text(item, "post_name").to_owned(),
+        link: text(item, None, "link").to_owned(),
+        guid: text(item, None, "guid").to_owned(),
+        post_type: wp_text(item, "post_type").to_owned(),
+        status: wp_text(item, "status").to_owned(),
+        created,
+        updated,
+        content: text(item, Some(CONTENT_NAMESPACE), "encoded").to_owned(),
+        excerpt: excerpt.unwrap_or_default().trim().to_owned(),
+        tags,
+    }
+}
+
+// Text of the first child with the given name, empty when there is none
+fn text<'a>(node: Node<'a, '_>, namespace: Option<&str>, name: &str) -> &'a str {
+    node.children()
+        .find(|child| {
+            child.tag_name().name() == name
+                && match (namespace, child.tag_name().namespace()) {
+                    (Some(namespace), Some(ns)) => ns.starts_with(namespace),
+                    (None, None) => true,
+                    _ => false,
+                }
+        })
+        .and_then(|child| child.text())
+        .unwrap_or_default()
+}
+
+fn wp_text<'a>(node: Node<'a, '_>, name: &str) -> &'a str {
+    text(node, Some(WP_NAMESPACE), name).trim()
+}
+
+// Prefers the GMT date, the other one is in the timezone the blog was set to
+fn parse_date(gmt: &str, local: &str) -> Option<i64> {
+    let parse = |date: &str| match date {
+        "" | ZERO_DATE => None,
+        date => NaiveDateTime::parse_from_str(date, "%Y-%m-%d %H:%M:%S").ok(),
+    };
+
+    match parse(gmt) {
+        Some(gmt) => Some(gmt.and_utc().timestamp()),
+        None => parse(local)
+            .and_then(|local| Local.from_local_datetime(&local).earliest())
+            .map(|local| local.timestamp()),
+    }
+}
+
+// Content with the tags dropped, good enough to make an excerpt from
+pub fn strip_tags(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    let mut in_tag = false;
+
+    for c in html.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => in_tag = false,
+            c if !in_tag => text.push(c),
+            _ => {}
+        }
+    }
+
+    text
+}
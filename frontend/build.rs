@@ -10,6 +10,13 @@ fn main() -> Result<()> {
 }
 
 fn build_proto() -> Result<()> {
-    compile_protos(&["../proto/search.proto"], &["../proto/"])?;
+    compile_protos(
+        &[
+            "../proto/search.proto",
+            "../proto/article.proto",
+            "../proto/media.proto",
+        ],
+        &["../proto/"],
+    )?;
     Ok(())
 }
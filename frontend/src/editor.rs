@@ -1,10 +1,24 @@
+use std::borrow::Cow;
+
+use ammonia::Builder;
 use anyhow::{anyhow, Result};
-use wasm_bindgen::{closure::Closure, JsCast};
-use web_sys::{console, Event, HtmlElement, HtmlTextAreaElement};
+use js_sys::{encode_uri_component, Date};
+use prost::Message;
+use wasm_bindgen::{closure::Closure, JsCast, JsValue};
+use wasm_bindgen_futures::spawn_local;
+use web_sys::{console, Event, HtmlElement, HtmlInputElement, HtmlTextAreaElement};
 use yew::{html, Callback, Component, Context, Html, Renderer};
 
 use crate::util::*;
 
+// The revision messages are only used by the backend for now
+#[allow(dead_code)]
+mod proto {
+    include!(concat!(env!("OUT_DIR"), "/blog.article.rs"));
+}
+
+use proto::{Article, Kind, Status, Tags};
+
 pub struct MarkdownRenderer;
 
 impl Component for MarkdownRenderer {
@@ -20,6 +34,23 @@ impl Component for MarkdownRenderer {
             console::error_1(&format!("Failed to add on change hook: {e}").into());
         }
 
+        if let Err(e) = add_on_save("editor-save-draft", Status::Draft) {
+            console::error_1(&format!("Failed to add save draft hook: {e}").into());
+        }
+
+        if let Err(e) = add_on_save("editor-publish", Status::Published) {
+            console::error_1(&format!("Failed to add publish hook: {e}").into());
+        }
+
+        if let Err(e) = fill_scheduled() {
+            console::error_1(&format!("Failed to fill in schedule: {e}").into());
+        }
+
+        // The server may have filled in an existing article
+        if let Err(e) = on_input_impl() {
+            console::error_1(&format!("Failed to render initial preview: {e}").into());
+        }
+
         html! {}
     }
 }
@@ -57,21 +88,247 @@ fn add_on_input() -> Result<()> {
 }
 
 fn on_input_impl() -> Result<()> {
-    use pulldown_cmark::{html, Parser};
+    let markdown_input = get_textarea_value("editor-markdown")?;
 
-    let markdown_input = get_element_by_id("editor-markdown")?
-        .dyn_into::<HtmlTextAreaElement>()
-        .map_err(|e| anyhow!("failed to process input box: {e:?}"))?
-        .value();
+    get_elements_by_class_name("markdown-body")?
+        .first()
+        .ok_or(anyhow!("failed to get markdown body"))?
+        .set_inner_html(&render_markdown(&markdown_input));
+
+    Ok(())
+}
+
+fn add_on_save(button: &str, status: Status) -> Result<()> {
+    let on_save = Callback::from(move |_| {
+        spawn_local(async move {
+            let text = match on_save_impl(status).await {
+                Ok(Status::Draft) => "Draft saved".to_owned(),
+                Ok(Status::Published) => "Published".to_owned(),
+                Ok(Status::Unlisted) => "Published as unlisted".to_owned(),
+                Ok(Status::Scheduled) => "Scheduled".to_owned(),
+                Err(e) => {
+                    console::error_1(&format!("Failed to save article: {e}").into());
+                    format!("Failed to save: {e}")
+                }
+            };
+
+            if let Err(e) = set_status(&text) {
+                console::error_1(&format!("Failed to update status: {e}").into());
+            }
+        });
+    });
+
+    let closure = Closure::wrap(Box::new(move |_: Event| {
+        on_save.emit(());
+    }) as Box<dyn FnMut(_)>);
+
+    get_element_by_id(button)?
+        .add_event_listener_with_callback("click", closure.as_ref().unchecked_ref())
+        .map_err(|e| anyhow!("cannot add event listener: {e:?}"))?;
+
+    closure.forget();
+
+    Ok(())
+}
+
+async fn on_save_impl(status: Status) -> Result<Status> {
+    let editor = get_element_by_id("editor")?;
+
+    // Set by the server when editing an existing article
+    let original_url = editor.get_attribute("data-url");
+
+    // The server renders `content` itself
+    let markdown = get_textarea_value("editor-markdown")?;
+
+    let tags = get_input_value("editor-article-tags")?
+        .split(',')
+        .map(|tag| tag.trim().to_owned())
+        .filter(|tag| !tag.is_empty())
+        .collect();
+
+    // Publishing honours the visibility options, a draft ignores them
+    let (status, scheduled) = match status {
+        Status::Published => match get_scheduled()? {
+            Some(scheduled) => (Status::Scheduled, Some(scheduled)),
+            None if get_checkbox_value("editor-article-unlisted")? => (Status::Unlisted, None),
+            None => (Status::Published, None),
+        },
+        status => (status, None),
+    };
+
+    let kind = match get_checkbox_value("editor-article-page")? {
+        true => Kind::Page,
+        false => Kind::Post,
+    };
+
+    let article = Article {
+        title: Some(get_input_value("editor-article-title")?),
+        url: get_input_value("editor-article-url")?,
+        markdown: Some(markdown),
+        excerpt: Some(get_textarea_value("editor-article-excerpt")?),
+        status: Some(status.into()),
+        tags: Some(Tags { tags }),
+        scheduled,
+        kind: Some(kind.into()),
+        ..Default::default()
+    };
+
+    let body = article.encode_to_vec();
+
+    let response = match original_url {
+        Some(url) => {
+            let url = format!("/api/article/{}", encode_uri_component(&url));
+            fetch("PUT", &url, Some(body)).await?
+        }
+        None => fetch("POST", "/api/article", Some(body)).await?,
+    };
+
+    let article = Article::decode(response.as_slice())?;
 
-    let parser = Parser::new(&markdown_input);
+    // Further saves should update the article we just wrote
+    editor
+        .set_attribute("data-url", &article.url)
+        .map_err(|e| anyhow!("cannot set article url: {e:?}"))?;
+
+    let preview = format!("/editor/preview/{}", encode_uri_component(&article.url));
+    get_element_by_id("editor-preview")?
+        .set_attribute("href", &preview)
+        .map_err(|e| anyhow!("cannot set preview link: {e:?}"))?;
+
+    let location = format!("/editor?url={}", encode_uri_component(&article.url));
+    get_window()?
+        .history()
+        .map_err(|e| anyhow!("failed to get history: {e:?}"))?
+        .replace_state_with_url(&JsValue::NULL, "", Some(&location))
+        .map_err(|e| anyhow!("failed to update location: {e:?}"))?;
+
+    Ok(article.status())
+}
+
+// The schedule as a unix timestamp, unless it is empty or already passed
+fn get_scheduled() -> Result<Option<i64>> {
+    let value = get_input_value("editor-article-scheduled")?;
+    if value.is_empty() {
+        return Ok(None);
+    }
+
+    // `datetime-local` values are parsed in the browser's time zone
+    let millis = Date::parse(&value);
+    if millis.is_nan() {
+        return Err(anyhow!("invalid publish time {value}"));
+    }
+
+    match millis > Date::now() {
+        true => Ok(Some((millis / 1000.0) as i64)),
+        false => Ok(None),
+    }
+}
+
+// The server only knows the timestamp, show it in the browser's time zone
+fn fill_scheduled() -> Result<()> {
+    let input = get_element_by_id("editor-article-scheduled")?
+        .dyn_into::<HtmlInputElement>()
+        .map_err(|e| anyhow!("failed to process input box: {e:?}"))?;
+
+    let timestamp = match input.get_attribute("data-timestamp") {
+        Some(timestamp) => timestamp.parse::<f64>()?,
+        None => return Ok(()),
+    };
+
+    let date = Date::new(&JsValue::from_f64(timestamp * 1000.0));
+    let value = format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}",
+        date.get_full_year(),
+        date.get_month() + 1,
+        date.get_date(),
+        date.get_hours(),
+        date.get_minutes()
+    );
+
+    input.set_value(&value);
+
+    Ok(())
+}
+
+fn render_markdown(markdown: &str) -> String {
+    use pulldown_cmark::{html, Options, Parser};
+
+    // Keep in sync with the backend so the preview matches the published article
+    let options = Options::ENABLE_TABLES
+        | Options::ENABLE_FOOTNOTES
+        | Options::ENABLE_STRIKETHROUGH
+        | Options::ENABLE_TASKLISTS
+        | Options::ENABLE_HEADING_ATTRIBUTES;
+
+    let parser = Parser::new_ext(markdown, options);
     let mut html_output = String::new();
     html::push_html(&mut html_output, parser);
 
-    get_elements_by_class_name("markdown-body")?
-        .get(0)
-        .ok_or(anyhow!("failed to get markdown body"))?
-        .set_inner_html(&html_output);
+    // Whatever an author wrote runs in the editor of whoever opens the article
+    sanitizer().clean(&html_output).to_string()
+}
+
+// Keep in sync with `markdown::sanitize` in the backend
+fn sanitizer() -> Builder<'static> {
+    let mut builder = Builder::default();
+
+    builder
+        .add_tags(&["input"])
+        .add_tag_attributes("input", &["type", "checked", "disabled"])
+        .add_tag_attributes("th", &["style"])
+        .add_tag_attributes("td", &["style"])
+        .add_tag_attributes("code", &["class"])
+        .add_tag_attributes("sup", &["class"])
+        .add_tag_attributes("div", &["class", "id"])
+        .attribute_filter(|element, attribute, value| match (element, attribute) {
+            ("th" | "td", "style") => match value {
+                "text-align: left" | "text-align: center" | "text-align: right" => {
+                    Some(Cow::Borrowed(value))
+                }
+                _ => None,
+            },
+            ("input", "type") => match value {
+                "checkbox" => Some(Cow::Borrowed(value)),
+                _ => None,
+            },
+            _ => Some(Cow::Borrowed(value)),
+        });
+
+    for heading in ["h1", "h2", "h3", "h4", "h5", "h6"] {
+        builder.add_tag_attributes(heading, &["id"]);
+    }
 
+    builder
+}
+
+fn set_status(text: &str) -> Result<()> {
+    get_element_by_id("editor-status")?.set_text_content(Some(text));
     Ok(())
 }
+
+fn get_input_value(id: &str) -> Result<String> {
+    let value = get_element_by_id(id)?
+        .dyn_into::<HtmlInputElement>()
+        .map_err(|e| anyhow!("failed to process input box: {e:?}"))?
+        .value();
+
+    Ok(value)
+}
+
+fn get_checkbox_value(id: &str) -> Result<bool> {
+    let checked = get_element_by_id(id)?
+        .dyn_into::<HtmlInputElement>()
+        .map_err(|e| anyhow!("failed to process checkbox: {e:?}"))?
+        .checked();
+
+    Ok(checked)
+}
+
+fn get_textarea_value(id: &str) -> Result<String> {
+    let value = get_element_by_id(id)?
+        .dyn_into::<HtmlTextAreaElement>()
+        .map_err(|e| anyhow!("failed to process input box: {e:?}"))?
+        .value();
+
+    Ok(value)
+}
@@ -1,7 +1,9 @@
 #![feature(try_blocks)]
 
 mod editor;
+mod media;
 mod scroll_to_top;
+mod search;
 mod util;
 
 use anyhow::{anyhow, Result};
@@ -9,7 +11,10 @@ use wasm_bindgen::prelude::wasm_bindgen;
 use web_sys::console;
 use yew::{html, Component, Context, Html, Renderer};
 
-use crate::{editor::MarkdownRenderer, scroll_to_top::ScrollToTop, util::*};
+use crate::{
+    editor::MarkdownRenderer, media::MediaPicker, scroll_to_top::ScrollToTop, search::Search,
+    util::*,
+};
 
 struct App;
 
@@ -50,6 +55,8 @@ fn start_impl() -> Result<()> {
 
     Renderer::<App>::with_root(app_element).render();
 
+    Search::start()?;
+
     let url = get_window()?
         .location()
         .pathname()
@@ -57,6 +64,7 @@ fn start_impl() -> Result<()> {
 
     if url == "/editor" {
         MarkdownRenderer::start()?;
+        MediaPicker::start()?;
     }
 
     Ok(())
@@ -0,0 +1,233 @@
+use anyhow::{anyhow, Result};
+use js_sys::Uint8Array;
+use prost::Message;
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{console, Event, File, HtmlInputElement, HtmlTextAreaElement};
+use yew::{html, Component, Context, Html, Renderer, TargetCast};
+
+use crate::util::*;
+
+mod proto {
+    include!(concat!(env!("OUT_DIR"), "/blog.media.rs"));
+}
+
+use proto::{Media, MediaList, UploadRequest};
+
+pub struct MediaPicker {
+    media: Vec<Media>,
+    open: bool,
+    status: String,
+}
+
+pub enum Msg {
+    Upload(File),
+    Uploaded(Result<(Media, String), String>),
+    Toggle,
+    Loaded(Vec<Media>),
+    Insert(usize),
+}
+
+impl Component for MediaPicker {
+    type Message = Msg;
+    type Properties = ();
+
+    fn create(_: &Context<Self>) -> Self {
+        Self {
+            media: vec![],
+            open: false,
+            status: String::new(),
+        }
+    }
+
+    fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
+        match msg {
+            Msg::Upload(file) => {
+                self.status = format!("Uploading {}", file.name());
+
+                ctx.link().send_future(async move {
+                    let alt = file_stem(&file.name());
+                    let result = upload(file).await.map(|media| (media, alt));
+                    Msg::Uploaded(result.map_err(|e| e.to_string()))
+                });
+
+                true
+            }
+            Msg::Uploaded(Ok((media, alt))) => {
+                self.status = String::new();
+
+                if let Err(e) = insert_markdown(&media, &alt) {
+                    console::error_1(&format!("Failed to insert media: {e}").into());
+                }
+
+                self.media.insert(0, media);
+
+                true
+            }
+            Msg::Uploaded(Err(e)) => {
+                console::error_1(&format!("Failed to upload media: {e}").into());
+                self.status = format!("Failed to upload: {e}");
+                true
+            }
+            Msg::Toggle => {
+                self.open = !self.open;
+
+                if self.open {
+                    ctx.link().send_future(async {
+                        let media = list().await.unwrap_or_else(|e| {
+                            console::error_1(&format!("Failed to list media: {e}").into());
+                            vec![]
+                        });
+
+                        Msg::Loaded(media)
+                    });
+                }
+
+                true
+            }
+            Msg::Loaded(media) => {
+                self.media = media;
+                true
+            }
+            Msg::Insert(i) => {
+                if let Err(e) = insert_markdown(&self.media[i], "") {
+                    console::error_1(&format!("Failed to insert media: {e}").into());
+                }
+
+                false
+            }
+        }
+    }
+
+    fn view(&self, ctx: &Context<Self>) -> Html {
+        let onchange = ctx.link().batch_callback(|e: Event| {
+            let input: HtmlInputElement = e.target_unchecked_into();
+            let file = input.files().and_then(|files| files.get(0));
+
+            // Allow picking the same file again
+            input.set_value("");
+
+            file.map(Msg::Upload)
+        });
+
+        let ontoggle = ctx.link().callback(|_| Msg::Toggle);
+
+        html! {
+            <div class="media-picker">
+                <label class="media-upload">
+                    { "Upload file" }
+                    <input type="file" {onchange} />
+                </label>
+                <button onclick={ontoggle}>{ "Media library" }</button>
+                <span class="media-status">{ &self.status }</span>
+                { self.view_library(ctx) }
+            </div>
+        }
+    }
+}
+
+impl MediaPicker {
+    pub fn start() -> Result<()> {
+        let media_element = get_element_by_id("editor-media")?;
+        Renderer::<Self>::with_root(media_element).render();
+        Ok(())
+    }
+
+    fn view_library(&self, ctx: &Context<Self>) -> Html {
+        if !self.open {
+            return html! {};
+        }
+
+        if self.media.is_empty() {
+            return html! {
+                <div class="media-library">
+                    <div class="media-empty">{ "Nothing uploaded yet" }</div>
+                </div>
+            };
+        }
+
+        let items = self.media.iter().enumerate().map(|(i, media)| {
+            let onclick = ctx.link().callback(move |_| Msg::Insert(i));
+
+            let preview = match media.mime_type.starts_with("image/") {
+                true => html! { <img src={media.url.clone()} alt={media.name.clone()} /> },
+                false => html! { <span>{ &media.name }</span> },
+            };
+
+            html! {
+                <button class="media-item" title={media.name.clone()} {onclick}>
+                    { preview }
+                </button>
+            }
+        });
+
+        html! {
+            <div class="media-library">
+                { for items }
+            </div>
+        }
+    }
+}
+
+async fn upload(file: File) -> Result<Media> {
+    let data = JsFuture::from(file.array_buffer())
+        .await
+        .map_err(|e| anyhow!("failed to read file: {e:?}"))?;
+
+    let request = UploadRequest {
+        name: file.name(),
+        data: Uint8Array::new(&data).to_vec(),
+    };
+
+    let response = fetch("POST", "/api/media", Some(request.encode_to_vec())).await?;
+
+    Ok(Media::decode(response.as_slice())?)
+}
+
+async fn list() -> Result<Vec<Media>> {
+    let response = fetch("GET", "/api/media", None).await?;
+    Ok(MediaList::decode(response.as_slice())?.media)
+}
+
+// Replaces the selection in the Markdown editor with a link to the media
+fn insert_markdown(media: &Media, alt: &str) -> Result<()> {
+    let markdown = match media.mime_type.starts_with("image/") {
+        true => format!("![{alt}]({})", media.url),
+        false => format!("[{}]({})", media.name, media.url),
+    };
+
+    let textarea = get_element_by_id("editor-markdown")?
+        .dyn_into::<HtmlTextAreaElement>()
+        .map_err(|e| anyhow!("failed to process input box: {e:?}"))?;
+
+    let start = textarea.selection_start().ok().flatten();
+    let end = textarea.selection_end().ok().flatten();
+    let length = textarea.value().encode_utf16().count() as u32;
+
+    textarea
+        .set_range_text_with_start_and_end_and_mode(
+            &markdown,
+            start.unwrap_or(length),
+            end.unwrap_or(length),
+            "end",
+        )
+        .map_err(|e| anyhow!("failed to insert text: {e:?}"))?;
+
+    // Let the preview pick up the change
+    let event = Event::new("input").map_err(|e| anyhow!("failed to create event: {e:?}"))?;
+    textarea
+        .dispatch_event(&event)
+        .map_err(|e| anyhow!("failed to dispatch event: {e:?}"))?;
+
+    Ok(())
+}
+
+// Used as alt text, brackets would end it early
+fn file_stem(name: &str) -> String {
+    let stem = match name.rsplit_once('.') {
+        Some((stem, _)) => stem,
+        None => name,
+    };
+
+    stem.replace(['[', ']'], "")
+}
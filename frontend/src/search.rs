@@ -0,0 +1,264 @@
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use js_sys::encode_uri_component;
+use prost::Message;
+use web_sys::{console, HtmlInputElement, KeyboardEvent};
+use yew::{
+    html, platform::time::sleep, AttrValue, Component, Context, Html, InputEvent, Renderer,
+    TargetCast,
+};
+
+use crate::util::*;
+
+mod proto {
+    include!(concat!(env!("OUT_DIR"), "/blog.search.rs"));
+}
+
+use proto::{SearchHit, SearchRequest, SearchResponse};
+
+const PROTOCOL_VERSION: u32 = 2;
+const DEBOUNCE: Duration = Duration::from_millis(300);
+const MAX_HITS: u32 = 8;
+
+pub struct Search {
+    query: String,
+    hits: Vec<SearchHit>,
+    selected: Option<usize>,
+    open: bool,
+    // Bumped on every keystroke so stale timers and responses are dropped
+    generation: u64,
+}
+
+pub enum Msg {
+    Input(String),
+    Fire(u64),
+    Results(u64, Vec<SearchHit>),
+    KeyDown(KeyboardEvent),
+    Close,
+}
+
+impl Component for Search {
+    type Message = Msg;
+    type Properties = ();
+
+    fn create(_: &Context<Self>) -> Self {
+        Self {
+            query: String::new(),
+            hits: vec![],
+            selected: None,
+            open: false,
+            generation: 0,
+        }
+    }
+
+    fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
+        match msg {
+            Msg::Input(query) => {
+                self.query = query;
+                self.generation += 1;
+
+                if self.query.trim().is_empty() {
+                    self.hits.clear();
+                    self.selected = None;
+                    self.open = false;
+                    return true;
+                }
+
+                let generation = self.generation;
+                ctx.link().send_future(async move {
+                    sleep(DEBOUNCE).await;
+                    Msg::Fire(generation)
+                });
+
+                false
+            }
+            Msg::Fire(generation) => {
+                if generation != self.generation {
+                    return false;
+                }
+
+                let query = self.query.clone();
+                ctx.link().send_future(async move {
+                    let hits = search(query).await.unwrap_or_else(|e| {
+                        console::error_1(&format!("Failed to search: {e}").into());
+                        vec![]
+                    });
+
+                    Msg::Results(generation, hits)
+                });
+
+                false
+            }
+            Msg::Results(generation, hits) => {
+                if generation != self.generation {
+                    return false;
+                }
+
+                self.hits = hits;
+                self.selected = None;
+                self.open = true;
+
+                true
+            }
+            Msg::KeyDown(event) => self.on_key_down(event),
+            Msg::Close => {
+                let changed = self.open;
+                self.open = false;
+                changed
+            }
+        }
+    }
+
+    fn view(&self, ctx: &Context<Self>) -> Html {
+        let oninput = ctx.link().callback(|e: InputEvent| {
+            let input: HtmlInputElement = e.target_unchecked_into();
+            Msg::Input(input.value())
+        });
+
+        let onkeydown = ctx.link().callback(Msg::KeyDown);
+        let onblur = ctx.link().callback(|_| Msg::Close);
+
+        html! {
+            <form class="search-form" action="/search" method="get">
+                <input
+                    type="search"
+                    name="q"
+                    placeholder="Search"
+                    autocomplete="off"
+                    value={self.query.clone()}
+                    {oninput}
+                    {onkeydown}
+                    {onblur}
+                />
+                { self.view_dropdown() }
+            </form>
+        }
+    }
+}
+
+impl Search {
+    pub fn start() -> Result<()> {
+        // A static export has no search box, there is no backend to ask
+        let search_element = match get_element_by_id("search") {
+            Ok(search_element) => search_element,
+            Err(_) => return Ok(()),
+        };
+
+        // Replace the plain form that is there for visitors without JavaScript
+        search_element.set_inner_html("");
+
+        Renderer::<Self>::with_root(search_element).render();
+
+        Ok(())
+    }
+
+    fn on_key_down(&mut self, event: KeyboardEvent) -> bool {
+        match event.key().as_str() {
+            "ArrowDown" if !self.hits.is_empty() => {
+                event.prevent_default();
+                self.open = true;
+                self.selected = Some(match self.selected {
+                    Some(i) if i + 1 < self.hits.len() => i + 1,
+                    _ => 0,
+                });
+                true
+            }
+            "ArrowUp" if !self.hits.is_empty() => {
+                event.prevent_default();
+                self.open = true;
+                self.selected = Some(match self.selected {
+                    Some(i) if i > 0 => i - 1,
+                    _ => self.hits.len() - 1,
+                });
+                true
+            }
+            "Enter" => {
+                let hit = match (self.open, self.selected) {
+                    (true, Some(i)) => &self.hits[i],
+                    // Let the form submit to the search page
+                    _ => return false,
+                };
+
+                event.prevent_default();
+
+                if let Err(e) = open_article(&hit.url) {
+                    console::error_1(&format!("Failed to open article: {e}").into());
+                }
+
+                false
+            }
+            "Escape" => {
+                self.open = false;
+                self.selected = None;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn view_dropdown(&self) -> Html {
+        if !self.open {
+            return html! {};
+        }
+
+        if self.hits.is_empty() {
+            return html! {
+                <div class="search-dropdown">
+                    <div class="search-empty">{ "No results" }</div>
+                </div>
+            };
+        }
+
+        let hits = self.hits.iter().enumerate().map(|(i, hit)| {
+            let class = match self.selected == Some(i) {
+                true => "search-hit search-hit-selected",
+                false => "search-hit",
+            };
+
+            let href = format!("/article/{}", encode_uri_component(&hit.url));
+
+            // The snippet is escaped by the server, apart from the <mark> tags
+            let snippet = Html::from_html_unchecked(AttrValue::from(hit.snippet.clone()));
+
+            html! {
+                // Navigate before the input loses focus and closes the dropdown
+                <a {class} {href} onmousedown={|e: yew::MouseEvent| e.prevent_default()}>
+                    <div class="search-hit-title">{ &hit.title }</div>
+                    <div class="search-hit-snippet">{ snippet }</div>
+                </a>
+            }
+        });
+
+        html! {
+            <div class="search-dropdown">
+                { for hits }
+            </div>
+        }
+    }
+}
+
+async fn search(query: String) -> Result<Vec<SearchHit>> {
+    let request = SearchRequest {
+        query,
+        version: PROTOCOL_VERSION,
+        limit: MAX_HITS,
+        ..Default::default()
+    };
+
+    let response = fetch("POST", "/api/search", Some(request.encode_to_vec())).await?;
+    let response = SearchResponse::decode(response.as_slice())?;
+
+    Ok(response.hits)
+}
+
+fn open_article(url: &str) -> Result<()> {
+    let href = format!("/article/{}", encode_uri_component(url));
+
+    get_window()?
+        .location()
+        .set_href(&href)
+        .map_err(|e| anyhow!("failed to navigate: {e:?}"))?;
+
+    Ok(())
+}
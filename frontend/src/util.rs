@@ -1,5 +1,8 @@
 use anyhow::{anyhow, Result};
-use web_sys::{window, Document, Element, Window};
+use js_sys::Uint8Array;
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{window, Document, Element, Request, RequestInit, Response, Window};
 
 pub fn get_window() -> Result<Window> {
     window().ok_or(anyhow!("failed to get window"))
@@ -26,3 +29,58 @@ pub fn get_elements_by_class_name(class: &str) -> Result<Vec<Element>> {
 
     Ok(elements)
 }
+
+pub async fn fetch(method: &str, url: &str, body: Option<Vec<u8>>) -> Result<Vec<u8>> {
+    let mut init = RequestInit::new();
+    init.method(method);
+
+    if let Some(body) = body {
+        let body = Uint8Array::from(body.as_slice());
+        init.body(Some(&body));
+    }
+
+    let request = Request::new_with_str_and_init(url, &init)
+        .map_err(|e| anyhow!("failed to build request: {e:?}"))?;
+
+    request
+        .headers()
+        .set("Content-Type", "application/octet-stream")
+        .map_err(|e| anyhow!("failed to set header: {e:?}"))?;
+
+    // The editor page carries the token of the session it was opened with
+    if method != "GET" {
+        let token = get_element_by_id("editor")
+            .ok()
+            .and_then(|editor| editor.get_attribute("data-csrf-token"));
+
+        if let Some(token) = token {
+            request
+                .headers()
+                .set("X-CSRF-Token", &token)
+                .map_err(|e| anyhow!("failed to set header: {e:?}"))?;
+        }
+    }
+
+    let response = JsFuture::from(get_window()?.fetch_with_request(&request))
+        .await
+        .map_err(|e| anyhow!("failed to send request: {e:?}"))?
+        .dyn_into::<Response>()
+        .map_err(|e| anyhow!("failed to process response: {e:?}"))?;
+
+    if !response.ok() {
+        return Err(anyhow!(
+            "request to {url} failed with status {}",
+            response.status()
+        ));
+    }
+
+    let buffer = response
+        .array_buffer()
+        .map_err(|e| anyhow!("failed to read response: {e:?}"))?;
+
+    let buffer = JsFuture::from(buffer)
+        .await
+        .map_err(|e| anyhow!("failed to read response: {e:?}"))?;
+
+    Ok(Uint8Array::new(&buffer).to_vec())
+}